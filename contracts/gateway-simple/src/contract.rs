@@ -1,10 +1,13 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
 use cosmwasm_std::{
     entry_point, DepsMut, Env, MessageInfo,
     ensure, Response, Deps, StdResult, Binary, to_binary, Empty,
 };
 
 
-use sdk::common::{ENCRYPTING_WALLET, BLOCK_SIZE};
+use sdk::common::{encryption_key_info, merge_batch_responses, EncryptionContext, BLOCK_SIZE};
+use sdk::traits::WithEncryption;
 use secret_toolkit::utils::{pad_handle_result, pad_query_result};
 
 
@@ -33,30 +36,83 @@ pub fn instantiate(
         .unwrap_or(info.sender.clone())
     )?;
 
+    sdk::common::set_key_grace_period(deps.storage, msg.key_grace_period)?;
+
     sdk::common::reset_encryption_wallet(
         deps.api, deps.storage, &env.block, None, None
     )?;
 
-    Ok(Response::new())
+    sdk::common::set_key_rotation_period(deps.storage, &env.block, msg.key_rotation_period)?;
+
+    sdk::common::set_nonce_validity(deps.storage, msg.nonce_validity)?;
+
+    sdk::session_key::set_max_session_duration(deps.storage, msg.max_session_duration)?;
+
+    sdk::feegrant::set_feegrant_config(deps.storage, msg.feegrant)?;
+
+    sdk::crypto::set_passkey_rp_id(deps.storage, msg.passkey_rp_id)?;
+
+    let register = sdk::fees::register_fee_tokens(
+        deps.api, deps.storage, &env, msg.fee_tokens.unwrap_or_default()
+    )?;
+
+    Ok(Response::new().add_messages(register))
 }
 
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     
     let (
-        msg, 
-        info
-    ) = sdk::common::handle_encrypted_wrapper(
-        deps.api, deps.storage, info, msg
+        msgs, 
+        info,
+        context
+    ) = sdk::common::handle_encrypted_batch(
+        deps.api, deps.storage, &env, info, msg
     )?;
 
-    let response = match msg {
+    // rotated after decrypting so that the current message is still accepted
+    let rotation = sdk::common::rotate_encryption_wallet_if_due(
+        deps.api, deps.storage, &env.block, None
+    )?;
+
+    // a failure of any message of a batch reverts the whole transaction
+    let response = msgs
+        .into_iter()
+        .map(|msg| execute_inner(deps.branch(), &env, &info, context.as_ref(), msg))
+        .collect::<Result<Vec<Response>, ContractError>>()
+        .and_then(|responses| Ok(merge_batch_responses(responses)?));
+
+    let response = match context {
+        Some(context) => response.and_then(|res| 
+            Ok(context.pay_relayer(context.encrypt_response(&env.block, res, &[])?))
+        ),
+        None => response
+    };
+
+    let response = match rotation {
+        Some(event) => response.map(|res| res.add_event(event)),
+        None => response
+    };
+
+    pad_handle_result(response, BLOCK_SIZE)
+}
+
+
+
+fn execute_inner(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    context: Option<&EncryptionContext>,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
 
         ExecuteMsg::ResetEncryptionKey {  } => {
             let admin = ADMIN.load(deps.storage)?;
@@ -67,17 +123,95 @@ pub fn execute(
             Ok(Response::default())
         },
 
+        ExecuteMsg::PruneNonces { limit } => {
+            Ok(sdk::common::handle_prune_nonces(deps.storage, &env.block, limit)?)
+        },
+
+        ExecuteMsg::CreateSessionKey { credential, expires, allowed } => {
+            Ok(sdk::session_key::handle_create_session_key(
+                deps.api, 
+                deps.storage, 
+                &env.block, 
+                info.sender.as_str(), 
+                context.map(|c| c.signer_id.as_slice()),
+                credential, 
+                expires, 
+                allowed
+            )?)
+        },
+
+        ExecuteMsg::RevokeSessionKey {  } => {
+            Ok(sdk::session_key::handle_revoke_session_key(deps.storage, info.sender.as_str())?)
+        },
+
+        ExecuteMsg::LinkCredentials { credentials, nonce } => {
+            Ok(sdk::accounts::handle_link_credentials(
+                deps.api, deps.storage, env, info.sender.as_str(), &nonce, credentials
+            )?)
+        },
+
+        ExecuteMsg::UnlinkCredentials { ids } => {
+            Ok(sdk::accounts::handle_unlink_credentials(deps.storage, info.sender.as_str(), ids)?)
+        },
+
+        ExecuteMsg::RevokeCredentials { ids } => {
+            let is_admin = ADMIN.load(deps.storage)? == info.sender;
+            Ok(sdk::revocation::handle_revoke_credentials(
+                deps.storage, 
+                &env.block, 
+                info.sender.as_str(), 
+                context.map(|c| c.signer_id.as_slice()), 
+                is_admin, 
+                ids
+            )?)
+        },
+
+        ExecuteMsg::RestoreCredentials { ids } => {
+            let admin = ADMIN.load(deps.storage)?;
+            ensure!(admin == info.sender, ContractError::Unauthorized {});
+            Ok(sdk::revocation::handle_restore_credentials(deps.storage, ids)?)
+        },
+
+        ExecuteMsg::DepositFees { recipient } => {
+            Ok(sdk::fees::handle_deposit_fees(
+                deps.storage, 
+                recipient.as_deref().unwrap_or(info.sender.as_str()), 
+                &info.funds
+            )?)
+        },
+
+        ExecuteMsg::WithdrawFees { token, amount, recipient } => {
+            Ok(sdk::fees::handle_withdraw_fees(
+                deps.storage, info.sender.as_str(), token, amount, recipient
+            )?)
+        },
+
+        ExecuteMsg::RequestFeegrant {  } => {
+            Ok(sdk::feegrant::handle_request_feegrant(
+                deps.api, deps.storage, env, info.sender.as_str(), None, context
+            )?)
+        },
+
+        ExecuteMsg::RevokeFeegrant {  } => {
+            Ok(sdk::feegrant::handle_revoke_feegrant(deps.storage, env, info.sender.as_str())?)
+        },
+
+        ExecuteMsg::Receive { from, amount, msg, .. } => {
+            Ok(sdk::fees::handle_receive_fees(
+                deps.storage, &info.sender, &from, amount, msg, context
+            )?)
+        },
+
         ExecuteMsg::Extension { msg } => {
             match msg {
                 InnerMethods::StoreSecret { text } => {
-                    SECRETS.insert(deps.storage, &info.sender.into_string(), &text)?;
+                    SECRETS.insert(deps.storage, &info.sender.to_string(), &text)?;
                     Ok(Response::default())
                 },
             }
         },
         ExecuteMsg::Encrypted { .. } => unreachable!(),
-    };
-    pad_handle_result(response, BLOCK_SIZE)
+    }
 }
 
 
@@ -87,10 +221,16 @@ pub fn execute(
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     let response = match msg {
 
-        QueryMsg::EncryptionKey {} =>  to_binary(&ENCRYPTING_WALLET.load(deps.storage)?.public_key),
+        QueryMsg::EncryptionKey {} =>  to_binary(&encryption_key_info(deps.storage)?),
+
+        QueryMsg::RevokedCredentials { page, page_size } => to_binary(
+            &sdk::revocation::revoked_credentials(deps.storage, page, page_size)?
+        ),
 
         QueryMsg::Extension { .. } =>  to_binary(&Empty {}),
 
+        QueryMsg::Encrypted { .. } => query::query_encrypted(deps, env, msg.encrypted()),
+
         _ => {
             match msg {
                 QueryMsg::WithPermit { 
@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
+use sdk::errors::EncryptionError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -30,6 +31,36 @@ pub enum ContractError {
 
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
+
+    #[error("ENC01: Nonce must be {expected} bytes long but is {actual}")]
+    InvalidNonceLength { expected: usize, actual: usize },
+
+    #[error("ENC02: Invalid public key: {msg}")]
+    InvalidPubkey { msg: String },
+
+    #[error("ENC03: Payload can't be decrypted with the given key, nonce and associated data")]
+    AeadFailure {},
+
+    #[error("ENC04: Payload hash doesn't match the payload")]
+    HashMismatch {},
+
+    #[error("ENC05: Invalid signature: {msg}")]
+    InvalidSignature { msg: String },
+
+    #[error("ENC06: Nonce already used")]
+    Replay {},
+
+    #[error("ENC07: Nested encryption is not allowed")]
+    NestedEncryption {},
+
+    #[error("ENC08: Payload is signed by {derived} instead of the declared {declared}")]
+    AddressMismatch { declared: String, derived: String },
+
+    #[error("ENC09: Message must be relayed by {expected} instead of {actual}")]
+    UnexpectedRelayer { expected: String, actual: String },
+
+    #[error("ENC10: Relayer fee of {required} exceeds the deposit of {available}")]
+    InsufficientFeeDeposit { required: Uint128, available: Uint128 },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -53,3 +84,21 @@ impl From<std::string::FromUtf8Error> for ContractError {
         Self::invalid_utf8(source)
     }
 }
+
+impl From<EncryptionError> for ContractError {
+    fn from(err: EncryptionError) -> Self {
+        match err {
+            EncryptionError::Std(err) => ContractError::Std(err),
+            EncryptionError::InvalidNonceLength { expected, actual } => ContractError::InvalidNonceLength { expected, actual },
+            EncryptionError::InvalidPubkey { msg } => ContractError::InvalidPubkey { msg },
+            EncryptionError::AeadFailure {} => ContractError::AeadFailure {},
+            EncryptionError::HashMismatch {} => ContractError::HashMismatch {},
+            EncryptionError::InvalidSignature { msg } => ContractError::InvalidSignature { msg },
+            EncryptionError::Replay {} => ContractError::Replay {},
+            EncryptionError::NestedEncryption {} => ContractError::NestedEncryption {},
+            EncryptionError::AddressMismatch { declared, derived } => ContractError::AddressMismatch { declared, derived },
+            EncryptionError::UnexpectedRelayer { expected, actual } => ContractError::UnexpectedRelayer { expected, actual },
+            EncryptionError::InsufficientFeeDeposit { required, available } => ContractError::InsufficientFeeDeposit { required, available },
+        }
+    }
+}
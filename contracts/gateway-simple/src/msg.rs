@@ -1,11 +1,18 @@
 use cosmwasm_schema::cw_serde;
 
-use sdk::gateway::{GatewayExecuteMsg, GatewayQueryMsg};
+use sdk::{feegrant::FeegrantConfig, fees::Snip20Token, gateway::{GatewayExecuteMsg, GatewayQueryMsg}, types::Duration};
 
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub  admin                  :   Option<String>,
+    pub  nonce_validity         :   Option<Duration>,
+    pub  key_grace_period       :   Option<Duration>,
+    pub  key_rotation_period    :   Option<Duration>,
+    pub  max_session_duration   :   Option<Duration>,
+    pub  feegrant               :   Option<FeegrantConfig>,
+    pub  fee_tokens             :   Option<Vec<Snip20Token>>,
+    pub  passkey_rp_id          :   Option<String>,
 }
 
 
@@ -20,6 +27,9 @@ pub enum InnerMethods {
 #[cw_serde]
 pub enum InnerQueries {
     GetSecret {},
+    LinkedCredentials {},
+    FeeBalance { token: Option<Snip20Token> },
+    FeegrantWallet {},
     Test {},
 }
 
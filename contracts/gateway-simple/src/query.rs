@@ -1,14 +1,21 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
 
 use cosmwasm_std::{
     to_binary, Binary, Deps, Env, StdResult
 };
 
-use sdk::{common::PERMIT_PREFIX, CosmosAuthData};
-//use sdk::{session_key::{SessionKey, SessionKeyStore}, CosmosAuthData};
+use sdk::{
+    accounts::{linked_account, linked_credentials},
+    common::{decrypt_query, encrypt_response, PERMIT_PREFIX}, 
+    fees::fee_balance,
+    feegrant::{feegrant_info, FeegrantWalletResponse},
+    session_key::{session_owner, variant_name}, 
+    CosmosAuthData, EncryptedParams
+};
 use secret_toolkit::permit::Permit;
 
 use crate::{state::{SECRETS}, msg::InnerQueries};
-//use shared::{storage::PERMIT_PREFIX, AccoundId};
 
 
 
@@ -37,13 +44,41 @@ pub fn query_with_auth_data(
     auth_data   :   CosmosAuthData,
     query       :   InnerQueries
 ) -> StdResult<Binary> {
-    auth_data.verify(deps.api)?;
-    let address = auth_data.primary_address(deps.api)?;
+    auth_data.verify(deps.api, deps.storage)?;
+    let primary_id = auth_data.primary_id();
+    let address = match session_owner(
+        deps.storage, 
+        &env.block, 
+        &primary_id, 
+        variant_name(&query)
+    )?.or_else(|| linked_account(deps.storage, &primary_id)) {
+        Some(address) => address,
+        None => auth_data.primary_address(deps.api)?
+    };
     query_inner(deps, env,address, query)
 }
 
 
 
+pub fn query_encrypted(
+    deps        :   Deps, 
+    env         :   Env, 
+    params      :   EncryptedParams,
+) -> StdResult<Binary> {
+    let (
+        query, 
+        address, 
+        key
+    ) = decrypt_query::<InnerQueries>(deps.api, deps.storage, &env, &params)?;
+
+    let block = env.block.clone();
+    let response = query_inner(deps, env, address, query)?;
+
+    to_binary(&encrypt_response(&key, &params.nonce, &block, &response)?)
+}
+
+
+
 pub fn query_inner(
     deps        :   Deps, 
     _env        :   Env, 
@@ -57,6 +92,15 @@ pub fn query_inner(
             &SECRETS
                 .get(deps.storage, &auth_user)
                 .unwrap_or_default()),
+        InnerQueries::LinkedCredentials {} => to_binary(
+            &linked_credentials(deps.storage, &auth_user)
+        ),
+        InnerQueries::FeeBalance { token } => to_binary(
+            &fee_balance(deps.storage, &auth_user, token.as_ref())
+        ),
+        InnerQueries::FeegrantWallet {} => to_binary(
+            &feegrant_info(deps.storage, &auth_user)?.map(FeegrantWalletResponse::from)
+        ),
     }
     
 }
\ No newline at end of file
@@ -9,6 +9,7 @@ pub const ADMIN             :    Item<Addr>              =    Item::new(b"admin"
 
 
 // a mapping of a account user addresses to their secrets
+#[allow(clippy::declare_interior_mutable_const)]
 pub const SECRETS               :    Keymap<String, String, Bincode2, WithoutIter>    
                                 =    KeymapBuilder::new(b"secrets").without_iter().build();
 
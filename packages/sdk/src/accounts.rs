@@ -0,0 +1,214 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{ensure, to_vec, Api, Binary, Env, Response, StdError, StdResult, Storage};
+use secret_toolkit::{
+    crypto::sha_256,
+    storage::{Keymap, KeymapBuilder, Keyset, KeysetBuilder, WithoutIter},
+    serialization::Bincode2
+};
+
+use crate::{crypto::utils::normalize_pubkey, Credential};
+
+
+
+/// Maximal number of credentials that can be linked to one account
+pub const MAX_LINKED_CREDENTIALS: usize = 16;
+
+
+// a mapping of credential ids to the accounts they are linked to
+#[allow(clippy::declare_interior_mutable_const)]
+pub const LINKED_ACCOUNTS       :    Keymap<Vec<u8>, String, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"linked_accounts").without_iter().build();
+
+// a mapping of accounts to the ids of their linked credentials
+#[allow(clippy::declare_interior_mutable_const)]
+pub const ACCOUNT_CREDENTIALS   :    Keymap<String, Vec<Vec<u8>>, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"account_credentials").without_iter().build();
+
+// a set of sha256 digests of link messages that have already been used
+#[allow(clippy::declare_interior_mutable_const)]
+pub const USED_LINK_MESSAGES    :    Keyset<Vec<u8>, Bincode2, WithoutIter>
+                                =    KeysetBuilder::new(b"used_link_messages").without_iter().build();
+
+
+
+/// Message that credentials sign to be linked to an account. Binds the signature to
+/// the gateway contract and the chain so that it can't be replayed anywhere else
+#[cw_serde]
+pub struct LinkAccountMessage {
+    /// account the credentials are linked to
+    pub link_account    :   String,
+    /// address of the gateway contract
+    pub contract        :   String,
+    /// id of the chain the gateway is deployed to
+    pub chain_id        :   String,
+    /// arbitrary string chosen by the client. Every message can only be used once
+    pub nonce           :   String,
+}
+
+
+
+/// Returns the JSON message that credentials must sign to be linked to the account
+/// with the given nonce. Fields are serialized in the order of [LinkAccountMessage]
+pub fn link_message(
+    env         :   &Env,
+    account     :   &str,
+    nonce       :   &str,
+) -> StdResult<String> {
+    let message = LinkAccountMessage {
+        link_account: account.to_string(),
+        contract: env.contract.address.to_string(),
+        chain_id: env.block.chain_id.clone(),
+        nonce: nonce.to_string(),
+    };
+    String::from_utf8(to_vec(&message)?).map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+
+
+/// Links credentials to the account so that any of them is resolved to the account id
+/// from now on. Every credential must be a valid signature of the [link_message] to prove
+/// that it's controlled by the owner of the account. The message is consumed and can't
+/// be used again. Returns ids of the new credentials
+pub fn link_credentials(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    env         :   &Env,
+    account     :   &str,
+    nonce       :   &str,
+    credentials :   Vec<Credential>,
+) -> StdResult<Vec<Vec<u8>>> {
+    ensure!(!credentials.is_empty(), StdError::generic_err("Credentials must not be empty"));
+
+    let message = link_message(env, account, nonce)?;
+    let digest = sha_256(message.as_bytes()).to_vec();
+    ensure!(
+        !USED_LINK_MESSAGES.contains(storage, &digest),
+        StdError::generic_err("Link message has already been used")
+    );
+
+    let account = account.to_string();
+    let mut linked = ACCOUNT_CREDENTIALS.get(storage, &account).unwrap_or_default();
+    let mut added = vec![];
+
+    for credential in credentials {
+        ensure!(
+            credential.signs(&message),
+            StdError::generic_err("Linked credentials must sign the link message")
+        );
+        let id = credential.id();
+        credential.verify(api, storage)?;
+
+        match LINKED_ACCOUNTS.get(storage, &id) {
+            Some(owner) if owner == account => continue,
+            Some(_) => return Err(StdError::generic_err("Credential is already linked to another account")),
+            None => {
+                LINKED_ACCOUNTS.insert(storage, &id, &account)?;
+                linked.push(id.clone());
+                added.push(id);
+            }
+        }
+    }
+
+    ensure!(
+        linked.len() <= MAX_LINKED_CREDENTIALS,
+        StdError::generic_err(format!("Can't link more than {} credentials", MAX_LINKED_CREDENTIALS))
+    );
+    ACCOUNT_CREDENTIALS.insert(storage, &account, &linked)?;
+    USED_LINK_MESSAGES.insert(storage, &digest)?;
+
+    Ok(added)
+}
+
+
+
+/// Removes links of the given credentials. All of them must be linked to the account
+pub fn unlink_credentials(
+    storage     :   &mut dyn Storage,
+    account     :   &str,
+    ids         :   Vec<Binary>,
+) -> StdResult<()> {
+    let account = account.to_string();
+    let mut linked = ACCOUNT_CREDENTIALS.get(storage, &account).unwrap_or_default();
+
+    for id in ids {
+        let id = normalize_pubkey(&id);
+        ensure!(
+            LINKED_ACCOUNTS.get(storage, &id).as_ref() == Some(&account),
+            StdError::generic_err("Credential is not linked to the account")
+        );
+        LINKED_ACCOUNTS.remove(storage, &id)?;
+        linked.retain(|l| l != &id);
+    }
+
+    if linked.is_empty() {
+        ACCOUNT_CREDENTIALS.remove(storage, &account)?;
+    } else {
+        ACCOUNT_CREDENTIALS.insert(storage, &account, &linked)?;
+    }
+    Ok(())
+}
+
+
+
+/// Returns the account the credential with the given id is linked to
+pub fn linked_account(
+    storage     :   &dyn Storage,
+    id          :   &[u8],
+) -> Option<String> {
+    LINKED_ACCOUNTS.get(storage, &normalize_pubkey(id))
+}
+
+
+
+/// Returns ids of all the credentials linked to the account
+pub fn linked_credentials(
+    storage     :   &dyn Storage,
+    account     :   &str,
+) -> Vec<Binary> {
+    ACCOUNT_CREDENTIALS
+        .get(storage, &account.to_string())
+        .unwrap_or_default()
+        .into_iter()
+        .map(Binary)
+        .collect()
+}
+
+
+
+pub fn handle_link_credentials(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    env         :   &Env,
+    account     :   &str,
+    nonce       :   &str,
+    credentials :   Vec<Credential>,
+) -> StdResult<Response> {
+    let added = link_credentials(api, storage, env, account, nonce, credentials)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "link_credentials")
+      .add_attribute("linked", added.len().to_string())
+    )
+}
+
+
+
+pub fn handle_unlink_credentials(
+    storage     :   &mut dyn Storage,
+    account     :   &str,
+    ids         :   Vec<Binary>,
+) -> StdResult<Response> {
+    let unlinked = ids.len();
+    unlink_credentials(storage, account, ids)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "unlink_credentials")
+      .add_attribute("unlinked", unlinked.to_string())
+    )
+}
+
+
+#[cfg(test)]
+mod tests;
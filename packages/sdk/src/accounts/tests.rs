@@ -0,0 +1,203 @@
+use ed25519_dalek::Signer;
+use cosmwasm_std::{
+    testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    to_binary, Addr, Binary, Empty, OwnedDeps, Storage
+};
+use crate::{
+    common::handle_encrypted_wrapper,
+    crypto::{client::{sign_eth_personal, EncryptedParamsBuilder}, pubkey_to_address, utils::secret_key_from_bytes},
+    gateway::GatewayExecuteMsg,
+    test_utils::{encrypted_test_builder, encrypted_test_setup, CONTRACT_PUBLIC, ED25519_PRIVATE, ETH_ADDRESS, ETH_PRIVATE, SIGN_HRP},
+    Credential, Ed25519Credential, EncryptedPayload, EthCredential
+};
+use super::*;
+
+
+type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+
+
+/// Address of the test user of [encrypted_test_builder]
+fn account() -> String {
+    pubkey_to_address(&encrypted_test_builder().signing_pubkey(), SIGN_HRP).unwrap()
+}
+
+
+/// Credential of the test Ethereum wallet signing the given message
+fn eth_credential(message: &str) -> Credential {
+    let eth_key = secret_key_from_bytes(&hex::decode(ETH_PRIVATE).unwrap()).unwrap();
+    EthCredential {
+        address: ETH_ADDRESS.to_string(),
+        signature: sign_eth_personal(&eth_key, message),
+        message: message.to_string(),
+        pubkey: None,
+    }.into()
+}
+
+
+/// Credential of the test ed25519 wallet signing the given message
+fn ed_credential(message: &str) -> Credential {
+    let ed_key = ed25519_dalek::SigningKey::from_bytes(&ED25519_PRIVATE);
+    Ed25519Credential {
+        pubkey: Binary(ed_key.verifying_key().to_bytes().to_vec()),
+        signature: Binary(ed_key.sign(message.as_bytes()).to_bytes().to_vec()),
+        message: message.to_string(),
+        format: None,
+    }.into()
+}
+
+
+/// Sender resolved by the gateway for a message signed by the test Ethereum wallet
+fn eth_sender(api: &dyn Api, storage: &mut dyn Storage) -> String {
+    let builder = EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &hex::decode(ETH_PRIVATE).unwrap()
+    ).unwrap();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = EncryptedPayload {
+        user_address: ETH_ADDRESS.to_string(),
+        user_pubkey: Binary::default(),
+        hrp: "eth".to_string(),
+        msg: to_binary(&inner).unwrap(),
+        kind: None,
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    };
+    handle_encrypted_wrapper(
+        api,
+        storage,
+        &mock_env(),
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<Option<Empty>>::from(builder.build(&payload).unwrap())
+    ).unwrap().1.sender.to_string()
+}
+
+
+
+/// Links the given credentials to the test account with the given nonce
+fn link(deps: &mut Deps, nonce: &str, credentials: Vec<Credential>) -> StdResult<Vec<Vec<u8>>> {
+    link_credentials(&deps.api, &mut deps.storage, &mock_env(), &account(), nonce, credentials)
+}
+
+
+/// Link message of the test account with the given nonce
+fn message(nonce: &str) -> String {
+    link_message(&mock_env(), &account(), nonce).unwrap()
+}
+
+
+
+#[test]
+fn link_message_format() {
+    let env = mock_env();
+    assert_eq!(
+        link_message(&env, "cosmos1account", "1").unwrap(),
+        format!(
+            r#"{{"link_account":"cosmos1account","contract":"{}","chain_id":"{}","nonce":"1"}}"#,
+            env.contract.address, env.block.chain_id
+        )
+    );
+}
+
+
+#[test]
+fn linked_credentials_must_sign_link_message() {
+    let mut deps = mock_dependencies();
+
+    // the bare account id isn't enough
+    assert!(link(&mut deps, "1", vec![eth_credential(&account())]).is_err());
+    // nor is a message with another nonce
+    assert!(link(&mut deps, "1", vec![eth_credential(&message("2"))]).is_err());
+    assert!(linked_credentials(&deps.storage, &account()).is_empty());
+}
+
+
+#[test]
+fn linked_credentials_are_bound_to_gateway() {
+    let mut deps = mock_dependencies();
+    let mut other = mock_env();
+    other.contract.address = Addr::unchecked("secret1othergateway");
+    let signed = eth_credential(&link_message(&other, &account(), "1").unwrap());
+
+    assert!(link(&mut deps, "1", vec![signed.clone()]).is_err());
+
+    let mut other = mock_env();
+    other.block.chain_id = "other-chain".to_string();
+    let signed = eth_credential(&link_message(&other, &account(), "1").unwrap());
+    assert!(link(&mut deps, "1", vec![signed]).is_err());
+}
+
+
+#[test]
+fn link_credentials_to_account() {
+    let mut deps = mock_dependencies();
+    let account = account();
+
+    let added = link(&mut deps, "1", vec![eth_credential(&message("1")), ed_credential(&message("1"))]).unwrap();
+    assert_eq!(added.len(), 2);
+    assert_eq!(linked_account(&deps.storage, &ed_credential("").id()), Some(account.clone()));
+    assert_eq!(linked_credentials(&deps.storage, &account).len(), 2);
+
+    // linking again is a no-op
+    let added = link(&mut deps, "2", vec![ed_credential(&message("2"))]).unwrap();
+    assert!(added.is_empty());
+}
+
+
+#[test]
+fn link_message_replay() {
+    let mut deps = mock_dependencies();
+    let account = account();
+    let signed = eth_credential(&message("1"));
+    link(&mut deps, "1", vec![signed.clone()]).unwrap();
+    unlink_credentials(&mut deps.storage, &account, vec![Binary(signed.id())]).unwrap();
+
+    // the unlinked credential can't be linked back with the old signature
+    assert!(link(&mut deps, "1", vec![signed]).is_err());
+    assert!(linked_credentials(&deps.storage, &account).is_empty());
+
+    link(&mut deps, "2", vec![eth_credential(&message("2"))]).unwrap();
+    assert_eq!(linked_credentials(&deps.storage, &account).len(), 1);
+}
+
+
+#[test]
+fn linked_credentials_cannot_be_claimed() {
+    let mut deps = mock_dependencies();
+    link(&mut deps, "1", vec![eth_credential(&message("1"))]).unwrap();
+
+    let other = link_message(&mock_env(), "cosmos1other", "1").unwrap();
+    assert!(link_credentials(
+        &deps.api, &mut deps.storage, &mock_env(), "cosmos1other", "1", vec![eth_credential(&other)]
+    ).is_err());
+    assert_eq!(linked_account(&deps.storage, &eth_credential("").id()), Some(account()));
+}
+
+
+#[test]
+fn linked_wallet_acts_for_account() {
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    assert_eq!(eth_sender(&deps.api, &mut deps.storage), ETH_ADDRESS.to_lowercase());
+
+    link(&mut deps, "1", vec![eth_credential(&message("1"))]).unwrap();
+    assert_eq!(eth_sender(&deps.api, &mut deps.storage), account());
+}
+
+
+#[test]
+fn unlink_own_credentials() {
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+    let account = account();
+    link(&mut deps, "1", vec![eth_credential(&message("1")), ed_credential(&message("1"))]).unwrap();
+
+    // only linked credentials can be unlinked
+    assert!(unlink_credentials(&mut deps.storage, "cosmos1other", vec![Binary(eth_credential("").id())]).is_err());
+    unlink_credentials(&mut deps.storage, &account, vec![Binary(eth_credential("").id())]).unwrap();
+
+    assert_eq!(eth_sender(&deps.api, &mut deps.storage), ETH_ADDRESS.to_lowercase());
+    assert_eq!(linked_credentials(&deps.storage, &account), vec![Binary(ed_credential("").id())]);
+}
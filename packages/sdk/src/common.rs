@@ -1,5 +1,14 @@
 mod handle;
+mod keys;
+mod nonces;
+mod response;
 mod storage;
 
 pub use handle::*;
-pub use storage::*;
\ No newline at end of file
+pub use keys::*;
+pub use nonces::*;
+pub use response::*;
+pub use storage::*;
+
+#[cfg(test)]
+mod tests;
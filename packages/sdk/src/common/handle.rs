@@ -1,7 +1,22 @@
-use cosmwasm_schema::serde::de::DeserializeOwned;
-use cosmwasm_std::{Api, BlockInfo, Response, StdResult, Storage, StdError, ensure, from_binary, MessageInfo, Addr};
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{Api, Binary, BlockInfo, Event, Response, StdError, StdResult, Storage, ensure, from_binary, MessageInfo, Addr, Env};
+use secret_toolkit::crypto::sha_256;
 
-use crate::{crypto::{wallets::{generate_secret_wallet, SecretEncryptionWallet}, verify_arbitrary}, traits::WithEncryption, CosmosCredential, common::NONCES};
+use crate::{
+    crypto::{wallets::{generate_secret_wallet, SecretEncryptionWallet}, payload_hash, KeyContext}, 
+    accounts::linked_account, errors::EncryptionError, fees::charge_relayer_fee, revocation::ensure_not_revoked, session_key::{ensure_session_allowed, session_key, session_owner, variant_name}, traits::WithEncryption, 
+    EncryptedParams, EncryptedPayload
+};
+
+use super::{
+    keys::{rotate_encryption_wallet, prune_retired_wallets, wallet_for_epoch, is_rotation_due, current_epoch, next_epoch, RETIRED_PRUNE_BATCH},
+    nonces::{use_nonce, prune_nonces, NONCE_PRUNE_BATCH},
+    response::EncryptionContext
+};
+
+
+/// Maximal number of inner messages in a batched payload
+pub const MAX_BATCH_SIZE: usize = 16;
 
 
 
@@ -14,18 +29,22 @@ pub fn reset_encryption_wallet(
     hrp               :   Option<String>
 ) -> StdResult<SecretEncryptionWallet> {
 
+    // mixed with the epoch so that rotations in the same block get different keys
+    let mut seeded = block.clone();
+    let epoch = next_epoch(storage)?;
+    seeded.random = seeded.random.map(|random|
+        Binary(sha_256(&[random.as_slice(), &epoch.to_be_bytes()].concat()).to_vec())
+    );
+
     let wallet : SecretEncryptionWallet = generate_secret_wallet(
         api, 
-        block, 
+        &seeded, 
         None, 
         password, 
         hrp
     )?.into();
     
-    super::storage::ENCRYPTING_WALLET.save(
-        storage, 
-        &wallet
-    )?;
+    rotate_encryption_wallet(storage, block, wallet.clone())?;
 
     Ok(wallet)
 }
@@ -44,58 +63,286 @@ pub fn handle_reset_encyption_wallet(
 
     Ok(Response::new()
       .add_attribute("action", "reset_encryption_wallet")
+      .add_attribute("epoch", super::storage::KEY_EPOCH.load(storage)?.to_string())
+    )
+}
+
+
+
+/// Rotates the encrypting wallet using the randomness of the block if the rotation
+/// period has passed since the last rotation. Meant to be called lazily on every execute.
+/// Returns `encryption_key_rotated` event if the wallet has been rotated
+#[cfg(feature = "wallets")]
+pub fn rotate_encryption_wallet_if_due(
+    api               :   &dyn Api,
+    storage           :   &mut dyn Storage,
+    block             :   &BlockInfo,
+    hrp               :   Option<String>
+) -> StdResult<Option<Event>> {
+    if !is_rotation_due(storage, block)? {
+        return Ok(None);
+    }
+
+    reset_encryption_wallet(api, storage, block, None, hrp)?;
+    let info = super::keys::encryption_key_info(storage)?;
+
+    let mut event = Event::new("encryption_key_rotated")
+        .add_attribute("epoch", info.epoch.to_string())
+        .add_attribute("public_key", info.public_key.to_base64());
+
+    if let Some(expires) = info.expires {
+        event = event.add_attribute("expires", expires.to_string());
+    }
+
+    Ok(Some(event))
+}
+
+
+
+pub fn handle_prune_nonces(
+    storage           :   &mut dyn Storage,
+    block             :   &BlockInfo,
+    limit             :   Option<u32>,
+) -> StdResult<Response> {
+    let pruned = prune_nonces(storage, block, limit.unwrap_or(NONCE_PRUNE_BATCH))?;
+
+    Ok(Response::new()
+      .add_attribute("action", "prune_nonces")
+      .add_attribute("pruned", pruned.to_string())
     )
 }
 
 
 
+/// Checks that the hash matches the payload, decrypts it and verifies the signature of the hash.
+/// Returns the payload, the address of the signer or of the account it is linked to,
+/// the shared key and the id of the credential. Revoked credentials are rejected.
+/// The relayer is the sender of the message and is only known for executes
+fn decrypt_and_verify(
+    api     : &dyn Api,
+    storage : &dyn Storage,
+    env     : &Env,
+    params  : &EncryptedParams,
+    relayer : Option<&Addr>,
+) -> Result<(EncryptedPayload, String, Vec<u8>, Vec<u8>), EncryptionError> {
+    // the signature is only over the hash so it must be bound to the payload
+    ensure!(
+        params.payload_hash.as_slice() == payload_hash(
+            params.version, &params.payload, &params.nonce, &params.user_key
+        ).as_slice(),
+        EncryptionError::HashMismatch {}
+    );
+
+    let wallet = wallet_for_epoch(storage, &env.block, params.epoch)?;
+
+    let context = KeyContext {
+        contract    :   env.contract.address.to_string(),
+        chain_id    :   env.block.chain_id.clone(),
+        epoch       :   match params.epoch {
+            Some(epoch) => epoch,
+            None => current_epoch(storage)?
+        },
+    };
+
+    let aad = match &params.associated_data {
+        Some(associated) => associated.to_bytes(
+            env.contract.address.as_str(),
+            &env.block.chain_id,
+            relayer.map(|r| r.as_str())
+        )?,
+        None => vec![]
+    };
+
+    let (decrypted, key) = wallet.decrypt_params(params, &context, &aad)?;
+
+    let credential = decrypted.credential(env, params)?;
+    let id = credential.id();
+    ensure_not_revoked(storage, &id)?;
+
+    let signer = credential.verify(api, storage).map_err(EncryptionError::invalid_signature)?;
+    ensure!(
+        decrypted.declares_address(&signer),
+        EncryptionError::AddressMismatch { declared: decrypted.user_address.clone(), derived: signer }
+    );
+    // credentials linked to an account act on its behalf
+    let signer = linked_account(storage, &id).unwrap_or(signer);
+
+    Ok((decrypted, signer, key, id))
+}
+
+
+
+/// Decrypts and authenticates an encrypted message returning the inner message and
+/// [MessageInfo] with the address of the user. Payloads naming a relayer fee are rejected
+/// since the fee can only be paid through [EncryptionContext::pay_relayer]
 pub fn handle_encrypted_wrapper<E>(
     api     : &dyn Api,
     storage : &mut dyn Storage,
+    env     : &Env,
     info    : MessageInfo,
     msg     : E
-) -> Result<(E, MessageInfo), StdError> 
+) -> Result<(E, MessageInfo), EncryptionError> 
     where E: WithEncryption + DeserializeOwned 
 {
-    if msg.is_encrypted() {
-        let params = msg.encrypted();
+    let (msg, info, context) = handle_encrypted_wrapper_with_context(
+        api, storage, env, info, msg
+    )?;
+    ensure!(
+        context.as_ref().and_then(|c| c.relayer_fee.as_ref()).is_none(),
+        StdError::generic_err("Relayer fees must be paid with `handle_encrypted_wrapper_with_context`")
+    );
+    Ok((msg, info))
+}
 
-        ensure!(
-            !NONCES.contains(storage, &params.nonce.0),
-            StdError::generic_err("Nonce already used")
-        );
 
-        let wallet = super::storage::ENCRYPTING_WALLET.load(storage)?;
 
-        let decrypted  = wallet.decrypt_to_payload(
-            &params.payload,
-            &params.user_key,
-            &params.nonce,
-        )?;
+/// Same as [handle_encrypted_wrapper] but also returns [EncryptionContext] for
+/// encrypted messages that can be used for encrypting the response back to the user.
+/// Batched payloads are rejected and must be handled with [handle_encrypted_batch]
+pub fn handle_encrypted_wrapper_with_context<E>(
+    api     : &dyn Api,
+    storage : &mut dyn Storage,
+    env     : &Env,
+    info    : MessageInfo,
+    msg     : E
+) -> Result<(E, MessageInfo, Option<EncryptionContext>), EncryptionError> 
+    where E: WithEncryption + DeserializeOwned 
+{
+    let (
+        mut msgs, 
+        info, 
+        context
+    ) = handle_encrypted_batch(api, storage, env, info, msg)?;
+
+    ensure!(
+        msgs.len() == 1,
+        StdError::generic_err("Batched payloads must be handled with `handle_encrypted_batch`")
+    );
+    Ok((msgs.remove(0), info, context))
+}
+
+
 
-        let cred = CosmosCredential {
-            message : params.payload_hash,
-            signature  : params.payload_signature,
-            pubkey : decrypted.user_pubkey,
-            hrp : decrypted.hrp
-        };
+/// Inner messages of the decrypted payload. A JSON array is treated as a batch
+fn inner_messages<E>(
+    msg     : &Binary,
+) -> Result<Vec<E>, EncryptionError> 
+    where E: WithEncryption + DeserializeOwned 
+{
+    let msgs : Vec<E> = match msg.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[') => from_binary(msg)?,
+        _ => vec![from_binary(msg)?]
+    };
+    ensure!(
+        !msgs.is_empty() && msgs.len() <= MAX_BATCH_SIZE,
+        StdError::generic_err(format!("Batch must contain from 1 to {} messages", MAX_BATCH_SIZE))
+    );
+    ensure!(
+        msgs.iter().all(|msg| !msg.is_encrypted()),
+        EncryptionError::NestedEncryption {}
+    );
+    Ok(msgs)
+}
+
+
+
+/// Same as [handle_encrypted_wrapper_with_context] but also accepts batched payloads whose `msg`
+/// is a JSON array of inner messages signed together under one nonce. The messages are returned
+/// in order and share the same authenticated [MessageInfo]. Not encrypted messages are returned
+/// as a batch of one. The caller must execute all of them or fail to keep the batch atomic
+pub fn handle_encrypted_batch<E>(
+    api     : &dyn Api,
+    storage : &mut dyn Storage,
+    env     : &Env,
+    info    : MessageInfo,
+    msg     : E
+) -> Result<(Vec<E>, MessageInfo, Option<EncryptionContext>), EncryptionError> 
+    where E: WithEncryption + DeserializeOwned 
+{
+    if msg.is_encrypted() {
+        let params = msg.encrypted();
+
+        let (
+            decrypted, 
+            signer, 
+            key,
+            signer_id
+        ) = decrypt_and_verify(api, storage, env, &params, Some(&info.sender))?;
 
-        NONCES.insert(storage, &params.nonce.0)?;
+        use_nonce(storage, &env.block, &params.nonce, decrypted.issued_at)?;
+        prune_nonces(storage, &env.block, NONCE_PRUNE_BATCH)?;
+        prune_retired_wallets(storage, &env.block, RETIRED_PRUNE_BATCH)?;
 
-        let sender = verify_arbitrary(api, &cred)?;
+        let inner_msgs : Vec<E> = inner_messages(&decrypted.msg)?;
 
-        let inner_msg : E = from_binary(&decrypted.msg)?;
+        // session keys must be allowed to authorise every message of the batch
+        let owner = match session_key(storage, &env.block, &decrypted.user_pubkey)? {
+            Some(session) => {
+                let variants = inner_msgs
+                    .iter()
+                    .map(|msg| msg.session_variant())
+                    .collect::<Vec<Option<String>>>();
+                ensure_session_allowed(&session, &variants)?;
+                Some(session.owner)
+            },
+            None => None
+        };
+        // the deposit of the owner can only be spent by the owner
         ensure!(
-            !inner_msg.is_encrypted(), 
-            StdError::generic_err("Nested encryption is not allowed")
+            owner.is_none() || decrypted.fee.is_none(),
+            StdError::generic_err("Session keys can't pay relayer fees")
         );
+        let sender = owner.unwrap_or(signer);
+
+        let relayer_fee = match &decrypted.fee {
+            Some(fee) => charge_relayer_fee(storage, &info.sender, &sender, fee)?,
+            None => None
+        };
 
-        Ok((inner_msg, MessageInfo {
+        Ok((inner_msgs, MessageInfo {
             sender: Addr::unchecked(sender),
             funds: info.funds,
-        }))
+        }, Some(EncryptionContext {
+            key,
+            nonce: params.nonce,
+            signer_id,
+            relayer_fee,
+        })))
     } else {
-        Ok((msg, info))
+        Ok((vec![msg], info, None))
     }
    
 }
+
+
+
+/// Decrypts an encrypted query and authenticates the user who signed it.
+/// Returns the inner query, the address of the user and the shared key
+/// that must be used for encrypting the response with [super::encrypt_response].
+/// Relayer fees named in the payload are ignored since queries aren't relayed
+pub fn decrypt_query<Q>(
+    api     : &dyn Api,
+    storage : &dyn Storage,
+    env     : &Env,
+    params  : &EncryptedParams,
+) -> StdResult<(Q, String, Vec<u8>)> 
+    where Q: Serialize + DeserializeOwned
+{
+    let (
+        decrypted, 
+        signer, 
+        key,
+        _
+    ) = decrypt_and_verify(api, storage, env, params, None)?;
+
+    let query : Q = from_binary(&decrypted.msg)?;
+
+    let sender = session_owner(
+        storage, 
+        &env.block, 
+        &decrypted.user_pubkey, 
+        variant_name(&query)
+    )?.unwrap_or(signer);
+
+    Ok((query, sender, key))
+}
@@ -0,0 +1,191 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Binary, BlockInfo, StdError, StdResult, Storage};
+
+use crate::{crypto::wallets::SecretEncryptionWallet, types::{Duration, Expiration}};
+use super::storage::{
+    ENCRYPTING_WALLET, KEY_EPOCH, KEY_EXPIRATION, KEY_GRACE_PERIOD, KEY_ROTATION_PERIOD, RETIRED_QUEUE, RETIRED_WALLETS
+};
+
+
+/// Number of expired retired wallets destroyed on every encrypted message
+pub const RETIRED_PRUNE_BATCH: u32 = 5;
+
+
+
+/// Public key of the gateway that must be used for encrypting messages
+#[cw_serde]
+pub struct EncryptionKeyResponse {
+    /// Public key of the current encrypting wallet
+    pub public_key  :   Binary,
+    /// Epoch of the key that can be passed along with encrypted params
+    pub epoch       :   u32,
+    /// Moment after which the key is rotated automatically.
+    /// `None` if the key is valid until the next manual rotation
+    pub expires     :   Option<Expiration>,
+}
+
+
+
+/// Sets for how long retired keys can still be used for decryption after a rotation.
+/// Passing `None` makes the keys unusable right after they are rotated
+pub fn set_key_grace_period(
+    storage     :   &mut dyn Storage,
+    grace       :   Option<Duration>,
+) -> StdResult<()> {
+    match grace {
+        Some(grace) => KEY_GRACE_PERIOD.save(storage, &grace),
+        None => {
+            KEY_GRACE_PERIOD.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Makes the encrypting wallet due for rotation once the period has passed since the last one.
+/// Passing `None` disables automatic rotation
+pub fn set_key_rotation_period(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    period      :   Option<Duration>,
+) -> StdResult<()> {
+    match period {
+        Some(period) => {
+            KEY_ROTATION_PERIOD.save(storage, &period)?;
+            KEY_EXPIRATION.save(storage, &period.after(block))
+        },
+        None => {
+            KEY_ROTATION_PERIOD.remove(storage);
+            KEY_EXPIRATION.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Whether the current encrypting wallet is due for an automatic rotation
+pub fn is_rotation_due(
+    storage     :   &dyn Storage,
+    block       :   &BlockInfo,
+) -> StdResult<bool> {
+    Ok(KEY_EXPIRATION
+        .may_load(storage)?
+        .map(|expiration| expiration.is_expired(block))
+        .unwrap_or(false))
+}
+
+
+
+/// Replaces the encrypting wallet with a new one and starts a new epoch.
+/// The previous wallet is kept for the grace period if there is one
+pub fn rotate_encryption_wallet(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    wallet      :   SecretEncryptionWallet,
+) -> StdResult<u32> {
+    let epoch = next_epoch(storage)?;
+    if let Some(previous) = ENCRYPTING_WALLET.may_load(storage)? {
+        if let Some(grace) = KEY_GRACE_PERIOD.may_load(storage)? {
+            let expires = grace.after(block);
+            RETIRED_WALLETS.insert(storage, &(epoch - 1), &(previous, expires))?;
+            RETIRED_QUEUE.push_back(storage, &(epoch - 1, expires))?;
+        }
+    }
+
+    ENCRYPTING_WALLET.save(storage, &wallet)?;
+    KEY_EPOCH.save(storage, &epoch)?;
+    if let Some(period) = KEY_ROTATION_PERIOD.may_load(storage)? {
+        KEY_EXPIRATION.save(storage, &period.after(block))?;
+    }
+    prune_retired_wallets(storage, block, RETIRED_PRUNE_BATCH)?;
+
+    Ok(epoch)
+}
+
+
+
+/// Epoch that the next rotation of the encrypting wallet starts
+pub fn next_epoch(
+    storage     :   &dyn Storage,
+) -> StdResult<u32> {
+    Ok(match ENCRYPTING_WALLET.may_load(storage)? {
+        Some(_) => current_epoch(storage)? + 1,
+        None => 0
+    })
+}
+
+
+
+/// Epoch of the current encrypting wallet
+pub fn current_epoch(
+    storage     :   &dyn Storage,
+) -> StdResult<u32> {
+    Ok(KEY_EPOCH.may_load(storage)?.unwrap_or_default())
+}
+
+
+
+/// Returns the wallet for decrypting payloads encrypted to the key of the given epoch.
+/// The current wallet is returned if the epoch isn't specified
+pub fn wallet_for_epoch(
+    storage     :   &dyn Storage,
+    block       :   &BlockInfo,
+    epoch       :   Option<u32>,
+) -> StdResult<SecretEncryptionWallet> {
+    let current = current_epoch(storage)?;
+
+    match epoch {
+        None => ENCRYPTING_WALLET.load(storage),
+        Some(epoch) if epoch == current => ENCRYPTING_WALLET.load(storage),
+        Some(epoch) => {
+            let (wallet, expires) = RETIRED_WALLETS.get(storage, &epoch).ok_or_else(||
+                StdError::generic_err(format!("Encryption key of epoch {} is not available", epoch))
+            )?;
+            if expires.is_expired(block) {
+                return Err(StdError::generic_err(format!("Encryption key of epoch {} has expired", epoch)));
+            }
+            Ok(wallet)
+        }
+    }
+}
+
+
+
+/// Destroys up to `limit` retired wallets whose grace period is over starting from the oldest ones.
+/// Returns the number of destroyed wallets
+pub fn prune_retired_wallets(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    limit       :   u32,
+) -> StdResult<u32> {
+    let mut pruned = 0;
+
+    while pruned < limit && !RETIRED_QUEUE.is_empty(storage)? {
+        let (epoch, expires) = RETIRED_QUEUE.get_at(storage, 0)?;
+        if !expires.is_expired(block) {
+            break;
+        }
+        RETIRED_QUEUE.pop_front(storage)?;
+        RETIRED_WALLETS.remove(storage, &epoch)?;
+        pruned += 1;
+    }
+
+    Ok(pruned)
+}
+
+
+
+/// Information about the current encryption key
+pub fn encryption_key_info(
+    storage     :   &dyn Storage,
+) -> StdResult<EncryptionKeyResponse> {
+    Ok(EncryptionKeyResponse {
+        public_key  :   ENCRYPTING_WALLET.load(storage)?.public_key,
+        epoch       :   current_epoch(storage)?,
+        expires     :   KEY_EXPIRATION.may_load(storage)?,
+    })
+}
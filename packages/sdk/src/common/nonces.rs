@@ -0,0 +1,102 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_std::{ensure, BlockInfo, StdError, StdResult, Storage};
+
+use crate::{errors::EncryptionError, types::{Duration, Expiration}};
+use super::storage::{LEGACY_NONCES, NONCES, NONCE_QUEUE, NONCE_VALIDITY};
+
+
+/// Number of expired nonces removed on every encrypted message
+pub const NONCE_PRUNE_BATCH: u32 = 5;
+
+
+
+/// Sets a window in which encrypted payloads are considered valid.
+/// Passing `None` disables the window and makes used nonces never expire
+pub fn set_nonce_validity(
+    storage     :   &mut dyn Storage,
+    validity    :   Option<Duration>,
+) -> StdResult<()> {
+    match validity {
+        Some(validity) => NONCE_VALIDITY.save(storage, &validity),
+        None => {
+            NONCE_VALIDITY.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Checks that the nonce hasn't been used before and that the payload was issued
+/// within the validity window. Marks the nonce as used until the window is over
+pub fn use_nonce(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    nonce       :   &[u8],
+    issued_at   :   Option<Expiration>,
+) -> Result<(), EncryptionError> {
+    let nonce = nonce.to_vec();
+    let validity = NONCE_VALIDITY.may_load(storage)?;
+
+    ensure!(!LEGACY_NONCES.contains(storage, &nonce), EncryptionError::Replay {});
+
+    if let Some(expiration) = NONCES.get(storage, &nonce) {
+        // without a window there is nothing else protecting from replays
+        ensure!(
+            validity.is_some() && expiration.is_expired(block),
+            EncryptionError::Replay {}
+        );
+    }
+
+    let expiration = match validity {
+        Some(validity) => {
+            let issued_at = issued_at.ok_or_else(||
+                StdError::generic_err("Payload must specify when it was issued")
+            )?;
+            ensure!(
+                issued_at.is_expired(block),
+                StdError::generic_err("Payload is issued in the future")
+            );
+            ensure!(
+                !(issued_at + validity)?.is_expired(block),
+                StdError::generic_err("Payload is too old")
+            );
+            validity.after(block)
+        },
+        None => Expiration::Never {}
+    };
+
+    NONCES.insert(storage, &nonce, &expiration)?;
+    if !matches!(expiration, Expiration::Never {}) {
+        NONCE_QUEUE.push_back(storage, &(nonce, expiration))?;
+    }
+    Ok(())
+}
+
+
+
+/// Removes up to `limit` expired nonces starting from the oldest ones.
+/// Returns the number of removed nonces
+pub fn prune_nonces(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    limit       :   u32,
+) -> StdResult<u32> {
+    let mut pruned = 0;
+
+    while pruned < limit && !NONCE_QUEUE.is_empty(storage)? {
+        let (nonce, expiration) = NONCE_QUEUE.get_at(storage, 0)?;
+        if !expiration.is_expired(block) {
+            break;
+        }
+        NONCE_QUEUE.pop_front(storage)?;
+        // the nonce could have been re-used after expiring
+        if NONCES.get(storage, &nonce) == Some(expiration) {
+            NONCES.remove(storage, &nonce)?;
+        }
+        pruned += 1;
+    }
+
+    Ok(pruned)
+}
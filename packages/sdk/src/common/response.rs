@@ -0,0 +1,151 @@
+use std::ops::Deref;
+use cosmwasm_std::{Attribute, Binary, BlockInfo, CosmosMsg, Event, Response, StdResult, to_binary};
+use secret_toolkit::crypto::sha_256;
+
+use crate::{crypto::chacha20poly1305_encrypt, EncryptedResponse};
+
+
+
+/// Shared key and nonce of an encrypted message that can be used
+/// for encrypting the results back to the user
+pub struct EncryptionContext {
+    /// Key shared between the contract and the user_key of the message
+    pub key     :   Vec<u8>,
+    /// Nonce of the encrypted message
+    pub nonce   :   Binary,
+    /// Id of the credential that signed the message
+    pub signer_id : Vec<u8>,
+    /// Message paying the relayer fee charged from the deposit of the user
+    pub relayer_fee : Option<CosmosMsg>,
+}
+
+
+
+/// Derives a nonce for encrypting the response from the nonce of the request, 
+/// the current block and the index of encrypted item to avoid re-using it for the same key
+fn response_nonce(
+    request_nonce   : &[u8],
+    block           : &BlockInfo,
+    index           : u32,
+) -> Vec<u8> {
+    sha_256(&[
+        request_nonce,
+        &block.height.to_be_bytes(),
+        &block.time.nanos().to_be_bytes(),
+        &index.to_be_bytes(),
+    ].concat())[..12].to_vec()
+}
+
+
+
+fn encrypt_item(
+    key             : &impl Deref<Target = [u8]>,
+    request_nonce   : &impl Deref<Target = [u8]>,
+    block           : &BlockInfo,
+    index           : u32,
+    data            : &[u8],
+) -> StdResult<EncryptedResponse> {
+    let nonce = response_nonce(request_nonce, block, index);
+    let data = chacha20poly1305_encrypt(&data, key, &nonce)?;
+
+    Ok(EncryptedResponse {
+        data    :   Binary(data),
+        nonce   :   Binary(nonce),
+    })
+}
+
+
+
+/// Encrypts the data with a shared key so that only the user can read it.
+pub fn encrypt_response(
+    key             : &impl Deref<Target = [u8]>,
+    request_nonce   : &impl Deref<Target = [u8]>,
+    block           : &BlockInfo,
+    data            : &Binary,
+) -> StdResult<EncryptedResponse> {
+    encrypt_item(key, request_nonce, block, 0, data)
+}
+
+
+
+impl EncryptionContext {
+
+    /// Adds the message paying the relayer fee to the response if the user has named one
+    pub fn pay_relayer(
+        &self,
+        response    :   Response,
+    ) -> Response {
+        match &self.relayer_fee {
+            Some(msg) => response.add_message(msg.clone()),
+            None => response
+        }
+    }
+
+    /// Encrypts `Response::data` and values of the attributes with the given keys.
+    /// The data is replaced with serialized [EncryptedResponse] and attribute values
+    /// with base64 encoded nonce followed by the ciphertext
+    pub fn encrypt_response<T>(
+        &self,
+        block       :   &BlockInfo,
+        mut response:   Response<T>,
+        attributes  :   &[&str],
+    ) -> StdResult<Response<T>> {
+        if let Some(data) = response.data.take() {
+            response.data = Some(to_binary(
+                &encrypt_item(&self.key, &self.nonce, block, 0, &data)?
+            )?);
+        }
+
+        let mut index = 0;
+        response.attributes = response.attributes
+            .into_iter()
+            .map(|attr| {
+                if !attributes.contains(&attr.key.as_str()) {
+                    return Ok(attr);
+                }
+                index += 1;
+                let encrypted = encrypt_item(
+                    &self.key, &self.nonce, block, index, attr.value.as_bytes()
+                )?;
+                Ok(Attribute::new(
+                    attr.key, 
+                    Binary([encrypted.nonce.0, encrypted.data.0].concat()).to_base64()
+                ))
+            })
+            .collect::<StdResult<Vec<Attribute>>>()?;
+
+        Ok(response)
+    }
+}
+
+
+
+/// Merges the responses of the messages of a batch into one. Messages and events are kept
+/// in order, attributes of every item are moved into a `batch_item` event with its index
+/// and the data is replaced with a JSON list of the data of every item.
+/// A single response is returned as is
+pub fn merge_batch_responses<T>(
+    mut responses   :   Vec<Response<T>>,
+) -> StdResult<Response<T>> {
+    if responses.len() == 1 {
+        return Ok(responses.remove(0));
+    }
+
+    let mut merged = Response::new()
+        .add_attribute("action", "batch")
+        .add_attribute("size", responses.len().to_string());
+    let mut results : Vec<Option<Binary>> = Vec::with_capacity(responses.len());
+
+    for (index, response) in responses.into_iter().enumerate() {
+        merged.messages.extend(response.messages);
+        merged.events.push(
+            Event::new("batch_item")
+                .add_attribute("index", index.to_string())
+                .add_attributes(response.attributes)
+        );
+        merged.events.extend(response.events);
+        results.push(response.data);
+    }
+
+    Ok(merged.set_data(to_binary(&results)?))
+}
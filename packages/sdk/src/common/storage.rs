@@ -1,17 +1,56 @@
 use secret_toolkit::{
-    storage::{Item, Keyset, KeysetBuilder, WithoutIter}, 
+    storage::{Item, Keymap, KeymapBuilder, Keyset, KeysetBuilder, DequeStore, WithoutIter}, 
     serialization::Bincode2
 };
 
+use crate::types::{Duration, Expiration};
+
 
 pub const BLOCK_SIZE: usize = 256;
 pub const PERMIT_PREFIX : &str = "permits";
 
 
 
-pub const NONCES                :    Keyset<Vec<u8>, Bincode2, WithoutIter>    
+// nonces used before they started expiring. They are never forgotten
+#[allow(clippy::declare_interior_mutable_const)]
+pub const LEGACY_NONCES         :    Keyset<Vec<u8>, Bincode2, WithoutIter>    
                                 =    KeysetBuilder::new(b"cr_nonces").without_iter().build();
 
+// a mapping of used nonces to the moment they can be forgotten
+#[allow(clippy::declare_interior_mutable_const)]
+pub const NONCES                :    Keymap<Vec<u8>, Expiration, Bincode2, WithoutIter>    
+                                =    KeymapBuilder::new(b"cr_nonce_expirations").without_iter().build();
+
+// used nonces in the order of their expiration for pruning
+#[allow(clippy::declare_interior_mutable_const)]
+pub const NONCE_QUEUE           :    DequeStore<(Vec<u8>, Expiration)>    
+                                =    DequeStore::new(b"cr_nonce_queue");
+
+pub const NONCE_VALIDITY        :    Item<Duration>   =    Item::new(b"cr_nonce_validity");
+
 
 #[cfg(feature = "wallets")]
 pub const ENCRYPTING_WALLET     :    Item<crate::crypto::wallets::SecretEncryptionWallet>   =    Item::new(b"enc_wallet");
+
+// epoch of the current encrypting wallet increased on every rotation
+pub const KEY_EPOCH             :    Item<u32>   =    Item::new(b"enc_epoch");
+
+// time for which the previous wallets can still be used for decryption after a rotation
+pub const KEY_GRACE_PERIOD      :    Item<Duration>   =    Item::new(b"enc_grace_period");
+
+// period after which the encrypting wallet is rotated automatically
+pub const KEY_ROTATION_PERIOD   :    Item<Duration>   =    Item::new(b"enc_rotation_period");
+
+// moment after which the current encrypting wallet is due for rotation
+pub const KEY_EXPIRATION        :    Item<Expiration>   =    Item::new(b"enc_expiration");
+
+// a mapping of epochs to the retired wallets and the moment they are destroyed
+#[cfg(feature = "wallets")]
+#[allow(clippy::declare_interior_mutable_const)]
+pub const RETIRED_WALLETS       :    Keymap<u32, (crate::crypto::wallets::SecretEncryptionWallet, Expiration), Bincode2, WithoutIter>    
+                                =    KeymapBuilder::new(b"enc_retired").without_iter().build();
+
+// epochs of the retired wallets in the order of their retirement for pruning
+#[allow(clippy::declare_interior_mutable_const)]
+pub const RETIRED_QUEUE         :    DequeStore<(u32, Expiration)>    
+                                =    DequeStore::new(b"enc_retired_queue");
@@ -0,0 +1,515 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use crate::{
+    test_utils::{encrypted_test_builder, encrypted_test_payload, encrypted_test_setup},
+    types::{Duration, Expiration}
+};
+use super::*;
+
+
+const NONCE : &[u8] = b"nonce-nonce!";
+
+
+
+#[test]
+fn nonces_without_window() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    // issuance time is optional
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, None).is_ok());
+
+    // never expires
+    let mut later = env.block.clone();
+    later.height += 1_000_000;
+    later.time = later.time.plus_seconds(1_000_000);
+    assert!(use_nonce(&mut deps.storage, &later, NONCE, None).is_err());
+    assert_eq!(prune_nonces(&mut deps.storage, &later, 10).unwrap(), 0);
+}
+
+
+
+#[test]
+fn nonces_with_window() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    set_nonce_validity(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+
+    let now = Some(Expiration::AtHeight(env.block.height));
+
+    // issuance time is required
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, None).is_err());
+    // from the future
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, Some(Expiration::AtHeight(env.block.height + 1))).is_err());
+    // too old
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, Some(Expiration::AtHeight(env.block.height - 10))).is_err());
+    // wrong kind
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, Some(Expiration::AtTime(env.block.time))).is_err());
+
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, now).is_ok());
+    assert!(use_nonce(&mut deps.storage, &env.block, NONCE, now).is_err());
+    assert!(use_nonce(&mut deps.storage, &env.block, b"other-nonce!", now).is_ok());
+
+    // nothing to prune yet
+    assert_eq!(prune_nonces(&mut deps.storage, &env.block, 10).unwrap(), 0);
+
+    let mut later = env.block.clone();
+    later.height += 10;
+
+    // replay of an old payload is rejected by the window
+    assert!(use_nonce(&mut deps.storage, &later, NONCE, now).is_err());
+
+    assert_eq!(prune_nonces(&mut deps.storage, &later, 1).unwrap(), 1);
+    assert_eq!(prune_nonces(&mut deps.storage, &later, 10).unwrap(), 1);
+    assert_eq!(prune_nonces(&mut deps.storage, &later, 10).unwrap(), 0);
+    assert!(!NONCES.contains(&deps.storage, &NONCE.to_vec()));
+}
+
+
+
+#[test]
+fn legacy_nonces_are_kept() {
+    use crate::errors::EncryptionError;
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    // used before nonces started expiring
+    LEGACY_NONCES.insert(&mut deps.storage, &NONCE.to_vec()).unwrap();
+
+    assert_eq!(use_nonce(&mut deps.storage, &env.block, NONCE, None), Err(EncryptionError::Replay {}));
+
+    set_nonce_validity(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+    let mut later = env.block.clone();
+    later.height += 100;
+    assert_eq!(
+        use_nonce(&mut deps.storage, &later, NONCE, Some(Expiration::AtHeight(later.height))), 
+        Err(EncryptionError::Replay {})
+    );
+}
+
+
+
+fn random_wallet() -> crate::crypto::wallets::SecretEncryptionWallet {
+    let secret = crate::crypto::client::random_secret_key();
+    crate::crypto::wallets::SecretEncryptionWallet {
+        private_key: cosmwasm_std::Binary(secret.secret_bytes().to_vec()),
+        public_key: cosmwasm_std::Binary(
+            secret.public_key(&secp256k1::Secp256k1::signing_only()).serialize().to_vec()
+        ),
+    }
+}
+
+
+#[test]
+fn key_rotation_without_grace() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    let first = random_wallet();
+    assert_eq!(rotate_encryption_wallet(&mut deps.storage, &env.block, first.clone()).unwrap(), 0);
+    assert_eq!(wallet_for_epoch(&deps.storage, &env.block, Some(0)).unwrap(), first);
+
+    let second = random_wallet();
+    assert_eq!(rotate_encryption_wallet(&mut deps.storage, &env.block, second.clone()).unwrap(), 1);
+    assert_eq!(wallet_for_epoch(&deps.storage, &env.block, None).unwrap(), second);
+
+    // destroyed immediately
+    assert!(wallet_for_epoch(&deps.storage, &env.block, Some(0)).is_err());
+
+    let info = encryption_key_info(&deps.storage).unwrap();
+    assert_eq!(info.public_key, second.public_key);
+    assert_eq!(info.epoch, 1);
+}
+
+
+
+#[test]
+fn key_rotation_with_grace() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    set_key_grace_period(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+
+    let first = random_wallet();
+    rotate_encryption_wallet(&mut deps.storage, &env.block, first.clone()).unwrap();
+    rotate_encryption_wallet(&mut deps.storage, &env.block, random_wallet()).unwrap();
+
+    let mut later = env.block.clone();
+    later.height += 5;
+    assert_eq!(wallet_for_epoch(&deps.storage, &later, Some(0)).unwrap(), first);
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &later, RETIRED_PRUNE_BATCH).unwrap(), 0);
+
+    later.height += 10;
+    assert!(wallet_for_epoch(&deps.storage, &later, Some(0)).is_err());
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &later, 0).unwrap(), 0);
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &later, RETIRED_PRUNE_BATCH).unwrap(), 1);
+
+    // unknown epochs
+    assert!(wallet_for_epoch(&deps.storage, &later, Some(7)).is_err());
+}
+
+
+
+#[test]
+fn retired_wallets_pruned_in_order() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    set_key_grace_period(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+
+    let mut block = env.block.clone();
+    for _ in 0..=RETIRED_PRUNE_BATCH * 2 {
+        rotate_encryption_wallet(&mut deps.storage, &block, random_wallet()).unwrap();
+        block.height += 1;
+    }
+
+    // the first batch destroys the oldest wallets
+    block.height += 10;
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &block, RETIRED_PRUNE_BATCH).unwrap(), RETIRED_PRUNE_BATCH);
+    for epoch in 0..RETIRED_PRUNE_BATCH {
+        assert!(RETIRED_WALLETS.get(&deps.storage, &epoch).is_none());
+    }
+    assert!(RETIRED_WALLETS.get(&deps.storage, &RETIRED_PRUNE_BATCH).is_some());
+
+    // and the next one continues with the rest
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &block, RETIRED_PRUNE_BATCH).unwrap(), RETIRED_PRUNE_BATCH);
+    for epoch in 0..RETIRED_PRUNE_BATCH * 2 {
+        assert!(RETIRED_WALLETS.get(&deps.storage, &epoch).is_none());
+    }
+    assert_eq!(prune_retired_wallets(&mut deps.storage, &block, RETIRED_PRUNE_BATCH).unwrap(), 0);
+}
+
+
+
+fn hrp() -> Option<String> {
+    Some("secret".to_string())
+}
+
+
+#[test]
+fn scheduled_key_rotation() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    set_key_rotation_period(&mut deps.storage, &env.block, Some(Duration::Height(100))).unwrap();
+    rotate_encryption_wallet(&mut deps.storage, &env.block, random_wallet()).unwrap();
+
+    let info = encryption_key_info(&deps.storage).unwrap();
+    assert_eq!(info.expires, Some(Expiration::AtHeight(env.block.height + 100)));
+
+    // not due yet
+    let mut later = env.block.clone();
+    later.height += 50;
+    assert!(rotate_encryption_wallet_if_due(&deps.api, &mut deps.storage, &later, hrp()).unwrap().is_none());
+    assert_eq!(encryption_key_info(&deps.storage).unwrap(), info);
+
+    // rotated lazily and rescheduled
+    later.height += 60;
+    later.random = Some(cosmwasm_std::Binary(vec![7; 32]));
+    let event = rotate_encryption_wallet_if_due(&deps.api, &mut deps.storage, &later, hrp()).unwrap().unwrap();
+    assert_eq!(event.ty, "encryption_key_rotated");
+
+    let rotated = encryption_key_info(&deps.storage).unwrap();
+    assert_eq!(rotated.epoch, 1);
+    assert_ne!(rotated.public_key, info.public_key);
+    assert_eq!(rotated.expires, Some(Expiration::AtHeight(later.height + 100)));
+    assert!(rotate_encryption_wallet_if_due(&deps.api, &mut deps.storage, &later, hrp()).unwrap().is_none());
+
+    // disabled
+    set_key_rotation_period(&mut deps.storage, &later, None).unwrap();
+    later.height += 1_000;
+    assert!(rotate_encryption_wallet_if_due(&deps.api, &mut deps.storage, &later, hrp()).unwrap().is_none());
+    assert_eq!(encryption_key_info(&deps.storage).unwrap().expires, None);
+}
+
+
+
+#[test]
+fn rotations_in_same_block() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    set_key_grace_period(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+    set_key_rotation_period(&mut deps.storage, &env.block, Some(Duration::Height(100))).unwrap();
+    let first = reset_encryption_wallet(&deps.api, &mut deps.storage, &env.block, None, hrp()).unwrap();
+
+    // the scheduled rotation and a manual reset in one transaction
+    env.block.height += 100;
+    rotate_encryption_wallet_if_due(&deps.api, &mut deps.storage, &env.block, hrp()).unwrap().unwrap();
+    let third = reset_encryption_wallet(&deps.api, &mut deps.storage, &env.block, None, hrp()).unwrap();
+    let second = wallet_for_epoch(&deps.storage, &env.block, Some(1)).unwrap();
+
+    assert_eq!(current_epoch(&deps.storage).unwrap(), 2);
+    assert_ne!(first.private_key, second.private_key);
+    assert_ne!(second.private_key, third.private_key);
+    assert_ne!(first.private_key, third.private_key);
+}
+
+
+
+#[test]
+fn batched_inner_messages() {
+    use crate::{errors::EncryptionError, gateway::GatewayExecuteMsg, EncryptedParams};
+    use cosmwasm_std::{testing::mock_info, Binary, Response};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let first : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let second : GatewayExecuteMsg = GatewayExecuteMsg::ResetEncryptionKey {};
+    let batch_params = |msgs: &Vec<GatewayExecuteMsg>| builder.build(&crate::EncryptedPayload {
+        msg: cosmwasm_std::to_binary(msgs).unwrap(),
+        ..encrypted_test_payload(&builder, &first)
+    }).unwrap();
+
+    let batch = batch_params(&vec![first.clone(), second.clone()]);
+    let single = builder.build(&encrypted_test_payload(&builder, &first)).unwrap();
+
+    // not encrypted messages are passed through as a batch of one
+    assert_eq!(
+        handle_encrypted_batch(
+            &deps.api, &mut deps.storage, &mock_env(), mock_info("relayer", &[]), first.clone()
+        ).map(|(msgs, info, context)| (msgs, info.sender.to_string(), context.is_none())),
+        Ok((vec![first.clone()], "relayer".to_string(), true))
+    );
+
+    let mut batched = |params: &EncryptedParams| {
+        handle_encrypted_batch(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(msgs, info, _)| (msgs, info.sender.to_string()))
+    };
+
+    let user = encrypted_test_payload(&builder, &first).user_address;
+    assert_eq!(batched(&batch), Ok((vec![first.clone(), second.clone()], user.clone())));
+    assert_eq!(batched(&batch), Err(EncryptionError::Replay {}));
+
+    // a single message is a batch of one
+    assert_eq!(batched(&single), Ok((vec![first.clone()], user.clone())));
+
+    // empty, oversized and nested batches are rejected as a whole
+    let empty = batch_params(&vec![]);
+    assert!(matches!(batched(&empty), Err(EncryptionError::Std(_))));
+
+    let oversized = batch_params(&vec![first.clone(); MAX_BATCH_SIZE + 1]);
+    assert!(matches!(batched(&oversized), Err(EncryptionError::Std(_))));
+
+    let nested = GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(
+        builder.build(&encrypted_test_payload(&builder, &first)).unwrap()
+    );
+    let with_nested = batch_params(&vec![first.clone(), nested]);
+    assert_eq!(batched(&with_nested), Err(EncryptionError::NestedEncryption {}));
+
+    // batches must be explicitly supported by the caller
+    let other_batch = batch_params(&vec![first.clone(), second.clone()]);
+    assert!(handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(other_batch)
+    ).is_err());
+
+    // results of every item are kept in order
+    let responses : Vec<Response> = vec![
+        Response::new().add_attribute("action", "first").set_data(Binary(vec![1])),
+        Response::new().add_attribute("action", "second"),
+    ];
+    let merged = merge_batch_responses(responses).unwrap();
+    assert_eq!(merged.attributes[0].value, "batch");
+    assert_eq!(merged.events.len(), 2);
+    assert_eq!(merged.events[1].attributes[0].value, "1");
+    assert_eq!(merged.events[1].attributes[1].value, "second");
+    assert_eq!(
+        cosmwasm_std::from_binary::<Vec<Option<Binary>>>(&merged.data.unwrap()),
+        Ok(vec![Some(Binary(vec![1])), None])
+    );
+
+    let one = Response::<cosmwasm_std::Empty>::new().add_attribute("action", "first");
+    assert_eq!(merge_batch_responses(vec![one.clone()]), Ok(one));
+}
+
+
+
+#[cosmwasm_schema::cw_serde]
+enum TestQuery {
+    Secret {},
+}
+
+
+/// Ephemeral key of the client used to decrypt the responses
+const EPHEMERAL : [u8; 32] = [5u8; 32];
+
+
+fn query_builder() -> crate::crypto::client::EncryptedParamsBuilder {
+    encrypted_test_builder().ephemeral_key(&EPHEMERAL.to_vec()).unwrap()
+}
+
+
+fn query_params() -> crate::EncryptedParams {
+    let builder = query_builder();
+    builder.build(&crate::EncryptedPayload {
+        msg: cosmwasm_std::to_binary(&TestQuery::Secret {}).unwrap(),
+        ..encrypted_test_payload(&builder, &crate::gateway::GatewayExecuteMsg::Extension { msg: None })
+    }).unwrap()
+}
+
+
+#[test]
+fn decrypt_encrypted_query() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let (query, sender, _) = decrypt_query::<TestQuery>(&deps.api, &deps.storage, &env, &query_params()).unwrap();
+    assert_eq!(query, TestQuery::Secret {});
+    assert_eq!(sender, encrypted_test_payload(&query_builder(), &crate::gateway::GatewayExecuteMsg::Extension { msg: None }).user_address);
+}
+
+
+#[test]
+fn encrypted_query_with_tampered_hash() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let mut tampered = query_params();
+    tampered.payload_hash = cosmwasm_std::Binary(vec![0u8; 32]);
+    assert!(decrypt_query::<TestQuery>(&deps.api, &deps.storage, &env, &tampered).is_err());
+}
+
+
+#[test]
+fn encrypted_query_response() {
+    use crate::crypto::client::decrypt_response;
+    use cosmwasm_std::Binary;
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let params = query_params();
+    let (_, _, key) = decrypt_query::<TestQuery>(&deps.api, &deps.storage, &env, &params).unwrap();
+
+    let data = Binary(b"secret response".to_vec());
+    let response = encrypt_response(&key, &params.nonce, &env.block, &data).unwrap();
+    assert_ne!(response.data, data);
+    assert_ne!(response.nonce, params.nonce);
+
+    let decrypted = decrypt_response(&query_builder(), &response).unwrap();
+    assert_eq!(decrypted, data);
+}
+
+
+/// Context of an encrypted execute message of the test user sent with [EPHEMERAL] key
+fn execute_context(deps: &mut cosmwasm_std::OwnedDeps<
+    cosmwasm_std::MemoryStorage, cosmwasm_std::testing::MockApi, cosmwasm_std::testing::MockQuerier
+>) -> EncryptionContext {
+    use crate::gateway::GatewayExecuteMsg;
+    use cosmwasm_std::testing::mock_info;
+
+    encrypted_test_setup(&mut deps.storage);
+    let builder = query_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+
+    handle_encrypted_wrapper_with_context(
+        &deps.api,
+        &mut deps.storage,
+        &mock_env(),
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(
+            builder.build(&encrypted_test_payload(&builder, &inner)).unwrap()
+        )
+    ).unwrap().2.unwrap()
+}
+
+
+#[test]
+fn encrypted_execute_response_data() {
+    use crate::{crypto::client::decrypt_response, EncryptedResponse};
+    use cosmwasm_std::{Binary, Response};
+
+    let mut deps = mock_dependencies();
+    let context = execute_context(&mut deps);
+
+    let response : Response = Response::new()
+        .set_data(b"private data")
+        .add_attribute("action", "public");
+    let encrypted = context.encrypt_response(&mock_env().block, response, &[]).unwrap();
+
+    let data : EncryptedResponse = cosmwasm_std::from_binary(&encrypted.data.unwrap()).unwrap();
+    assert_eq!(
+        decrypt_response(&query_builder(), &data).unwrap(),
+        Binary(b"private data".to_vec())
+    );
+}
+
+
+#[test]
+fn encrypted_execute_response_attributes() {
+    use crate::crypto::client::decrypt_attribute;
+    use cosmwasm_std::Response;
+
+    let mut deps = mock_dependencies();
+    let context = execute_context(&mut deps);
+
+    let response : Response = Response::new()
+        .add_attribute("action", "public")
+        .add_attribute("secret", "private")
+        .add_attribute("other", "private too");
+    let encrypted = context.encrypt_response(&mock_env().block, response, &["secret", "other"]).unwrap();
+
+    assert_eq!(encrypted.attributes[0].value, "public");
+
+    let secret = &encrypted.attributes[1].value;
+    let other = &encrypted.attributes[2].value;
+    // different nonces for every item
+    assert_ne!(secret[..16], other[..16]);
+    assert_eq!(decrypt_attribute(&query_builder(), secret).unwrap(), "private");
+    assert_eq!(decrypt_attribute(&query_builder(), other).unwrap(), "private too");
+}
+
+
+#[test]
+fn versioned_response_round_trip() {
+    use crate::{
+        crypto::{client::{decrypt_attribute, decrypt_response}, PROTOCOL_V1},
+        gateway::GatewayExecuteMsg, EncryptedResponse
+    };
+    use cosmwasm_std::{testing::mock_info, Binary, Response};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = query_builder()
+        .version(PROTOCOL_V1)
+        .contract(env.contract.address.as_str(), &env.block.chain_id)
+        .epoch(0);
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+
+    let (_, _, context) = handle_encrypted_wrapper_with_context(
+        &deps.api,
+        &mut deps.storage,
+        &env,
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(
+            builder.build(&encrypted_test_payload(&builder, &inner)).unwrap()
+        )
+    ).unwrap();
+
+    let response : Response = Response::new()
+        .set_data(b"private data")
+        .add_attribute("secret", "private");
+    let encrypted = context.unwrap().encrypt_response(&env.block, response, &["secret"]).unwrap();
+
+    let data : EncryptedResponse = cosmwasm_std::from_binary(&encrypted.data.unwrap()).unwrap();
+    assert_eq!(decrypt_response(&builder, &data).unwrap(), Binary(b"private data".to_vec()));
+    assert_eq!(decrypt_attribute(&builder, &encrypted.attributes[0].value).unwrap(), "private");
+
+    // the raw shared key of the legacy version doesn't fit
+    assert!(decrypt_response(&query_builder(), &data).is_err());
+}
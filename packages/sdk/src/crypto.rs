@@ -1,24 +1,196 @@
-use crate::CosmosCredential;
-use cosmwasm_std::{Api, StdError, StdResult, CanonicalAddr, Binary};
-use secret_toolkit::crypto::sha_256;
+use crate::{
+    errors::EncryptionError, revocation::ensure_not_revoked, AddressScheme, AssociatedData, Cipher, CosmosCredential, Ed25519AddressFormat, Ed25519Credential, 
+    EthCredential, MultisigCredential, PasskeyCredential
+};
+use cosmwasm_std::{ensure, from_slice, Api, StdError, StdResult, Storage, CanonicalAddr, Binary};
+use secret_toolkit::{crypto::sha_256, storage::Item};
 use bech32::{Bech32, Hrp};
 use std::{ops::Deref, fmt::Display};
-use chacha20poly1305::{ChaCha20Poly1305, Nonce, aead::Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305, aead::{Aead, Payload, generic_array::GenericArray}, KeyInit};
+use aes_gcm_siv::Aes256GcmSiv;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
 
 
 pub mod utils;
-use utils::{ripemd160, preamble_msg_arb_036};
+pub mod eip712;
+use eip712::EncryptedTypedData;
+use utils::{
+    ripemd160, keccak256, preamble_msg_arb_036, preamble_msg_eth_191, 
+    public_key_from_bytes, to_base64url, encode_varint, hkdf_sha256
+};
+
+
+/// Amino prefix of `tendermint/PubKeySecp256k1`
+const AMINO_SECP256K1_PREFIX: [u8; 4] = [0xeb, 0x5a, 0xe9, 0x87];
+
+/// Amino prefix of `tendermint/PubKeyMultisigThreshold`
+const AMINO_MULTISIG_PREFIX: [u8; 4] = [0x22, 0xc1, 0xf7, 0xe2];
+
+/// Prefix of the addresses of accounts controlled by a threshold of credentials
+pub const THRESHOLD_HRP: &str = "secret";
+
+/// Prefixes of the chains using `ethsecp256k1` keys
+pub const ETH_SECP256K1_HRPS: [&str; 3] = ["inj", "evmos", "crc"];
+
+/// Encryption protocol using the raw ECDH secret as the key
+pub const PROTOCOL_V0: u8 = 0;
+
+/// Encryption protocol deriving the key from the ECDH secret with HKDF-SHA256
+/// bound to the gateway instance and the epoch of its key
+pub const PROTOCOL_V1: u8 = 1;
+
+/// Latest version of the encryption protocol
+pub const PROTOCOL_VERSION: u8 = PROTOCOL_V1;
+
+
+
+/// Gateway instance and epoch of its key that a derived encryption key is bound to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyContext {
+    /// Address of the gateway contract
+    pub contract    :   String,
+    /// Chain id of the network the gateway is deployed to
+    pub chain_id    :   String,
+    /// Epoch of the gateway key
+    pub epoch       :   u32,
+}
+
+
+impl KeyContext {
+    /// Context string used as HKDF `info`
+    pub fn info(&self) -> Vec<u8> {
+        format!(
+            "secret-cosmos-abstraction/v{}/{}/{}/{}", 
+            PROTOCOL_V1, self.chain_id, self.contract, self.epoch
+        ).into_bytes()
+    }
+}
+
+
+
+impl AddressScheme {
+    /// Default scheme of a chain with the given human readable prefix
+    pub fn from_hrp(hrp: &str) -> Self {
+        if ETH_SECP256K1_HRPS.contains(&hrp) {
+            AddressScheme::EthSecp256k1
+        } else {
+            AddressScheme::Cosmos
+        }
+    }
+
+    /// Hashes the sign bytes the way wallets of the scheme do before signing
+    pub fn digest(&self, sign_bytes: &[u8]) -> [u8; 32] {
+        match self {
+            AddressScheme::Cosmos => sha_256(sign_bytes),
+            AddressScheme::EthSecp256k1 => keccak256(sign_bytes),
+        }
+    }
+}
+
 
 
 /// Converts a public key to an account address with the given human readable prefix.
+/// The derivation scheme is chosen based on the prefix
 /// @param pubkey: &[u8] - The public key to convert.
 /// @param hrp: &str - The human readable prefix to use.
 /// @returns String - bech32 encoded account address
 pub fn pubkey_to_address(pubkey: &[u8], hrp: &str) -> StdResult<String> {
-    let bech32_addr = ripemd160(&sha_256(pubkey));
+    pubkey_to_address_with_scheme(pubkey, hrp, &AddressScheme::from_hrp(hrp))
+}
+
+
+/// Converts a public key to an account address with the given human readable prefix
+/// using the given derivation scheme.
+/// @param pubkey: &[u8] - The public key to convert.
+/// @param hrp: &str - The human readable prefix to use.
+/// @param scheme: &AddressScheme - The scheme for deriving the address bytes
+/// @returns String - bech32 encoded account address
+pub fn pubkey_to_address_with_scheme(pubkey: &[u8], hrp: &str, scheme: &AddressScheme) -> StdResult<String> {
+    let bech32_addr = match scheme {
+        AddressScheme::Cosmos => ripemd160(&sha_256(pubkey)),
+        AddressScheme::EthSecp256k1 => {
+            let uncompressed = public_key_from_bytes(&pubkey)?.serialize_uncompressed();
+            keccak256(&uncompressed[1..])[12..].to_vec()
+        }
+    };
+    encode_address(hrp, &bech32_addr)
+}
+
+
+/// Derives a stable account address of a passkey from its credential id.
+/// The public key is hashed together with the id so that nobody else can claim it
+/// @param credential_id: &[u8] - The id of the WebAuthn credential.
+/// @param pubkey: &[u8] - SEC1 encoded P-256 public key of the credential.
+/// @param hrp: &str - The human readable prefix to use.
+/// @returns String - bech32 encoded account address
+pub fn pubkey_to_passkey_address(credential_id: &[u8], pubkey: &[u8], hrp: &str) -> StdResult<String> {
+    let compressed = passkey_verifying_key(pubkey)?.to_encoded_point(true);
+    let bech32_addr = ripemd160(&sha_256(&[credential_id, compressed.as_bytes()].concat()));
+    encode_address(hrp, &bech32_addr)
+}
+
+
+/// Converts an ed25519 public key to an address of the given format.
+/// @param pubkey: &[u8] - 32 bytes ed25519 public key.
+/// @param format: &Ed25519AddressFormat - base58 (Solana) or hex (Near) encoding.
+/// @returns String - encoded public key
+pub fn pubkey_to_ed25519_address(pubkey: &[u8], format: &Ed25519AddressFormat) -> StdResult<String> {
+    ensure!(pubkey.len() == 32, StdError::generic_err("Ed25519 public key must be 32 bytes long"));
+    Ok(match format {
+        Ed25519AddressFormat::Base58 => bs58::encode(pubkey).into_string(),
+        Ed25519AddressFormat::Hex => hex::encode(pubkey),
+    })
+}
+
+
+/// Converts an identifier of a set of credentials to an address.
+/// @param key_set_id: &[u8] - hash of the credential ids and the threshold.
+/// @returns String - bech32 encoded address with [THRESHOLD_HRP] prefix
+pub fn key_set_to_address(key_set_id: &[u8]) -> StdResult<String> {
+    encode_address(THRESHOLD_HRP, &ripemd160(key_set_id))
+}
+
+
+/// Encodes an amino multisig public key (`LegacyAminoPubKey`) the way cosmos-sdk does for address derivation.
+/// @param threshold: u8 - Minimal number of signatures.
+/// @param pubkeys: &[Binary] - Compressed secp256k1 public keys of the members.
+/// @returns Vec<u8> - amino encoded multisig public key
+pub fn multisig_pubkey_to_amino(threshold: u8, pubkeys: &[Binary]) -> StdResult<Vec<u8>> {
+    let mut amino = AMINO_MULTISIG_PREFIX.to_vec();
+    amino.push(0x08);
+    amino.extend(encode_varint(threshold as u64));
+
+    for pubkey in pubkeys {
+        ensure!(
+            pubkey.len() == 33, 
+            StdError::generic_err("Multisig members must use compressed secp256k1 public keys")
+        );
+        let member = [AMINO_SECP256K1_PREFIX.as_slice(), &[33], pubkey].concat();
+        amino.push(0x12);
+        amino.extend(encode_varint(member.len() as u64));
+        amino.extend(member);
+    }
+
+    Ok(amino)
+}
+
+
+/// Converts an amino multisig public key to an account address with the given human readable prefix.
+/// Unlike single keys the address is the first 20 bytes of sha256 of the amino encoded key
+/// @param threshold: u8 - Minimal number of signatures.
+/// @param pubkeys: &[Binary] - Compressed secp256k1 public keys of the members.
+/// @param hrp: &str - The human readable prefix to use.
+/// @returns String - bech32 encoded account address
+pub fn multisig_to_address(threshold: u8, pubkeys: &[Binary], hrp: &str) -> StdResult<String> {
+    let amino = multisig_pubkey_to_amino(threshold, pubkeys)?;
+    encode_address(hrp, &sha_256(&amino)[..20])
+}
+
+
+fn encode_address(hrp: &str, bytes: &[u8]) -> StdResult<String> {
     let account: String = bech32::encode::<Bech32>(
         Hrp::parse(hrp).map_err(|e| StdError::generic_err(e.to_string()))?,
-        &bech32_addr
+        bytes
     ).unwrap();
     Ok(account)
 }
@@ -34,14 +206,26 @@ pub fn pubkey_to_canonical(pubkey: &[u8]) -> CanonicalAddr {
 
 
 
+/// Converts a secp256k1 public key to a 0x prefixed Ethereum address.
+/// @param pubkey: &[u8] - compressed or uncompressed public key
+/// @returns String - lowercase hex encoded address
+pub fn pubkey_to_eth_address(pubkey: &[u8]) -> StdResult<String> {
+    let uncompressed = public_key_from_bytes(&pubkey)?.serialize_uncompressed();
+    let hash = keccak256(&uncompressed[1..]);
+    Ok(format!("0x{}", hex::encode(&hash[12..])))
+}
+
+
+
 /// Verifies an arbitrary message (036) using passed public key, signature
-/// and human readable prefix.
-pub fn verify_arbitrary<M : Display>(api:  &dyn Api, cred: &CosmosCredential<M>) -> StdResult<String> {
-    
+/// and human readable prefix. Fails if the public key has been revoked.
+pub fn verify_arbitrary<M : Display>(api:  &dyn Api, storage: &dyn Storage, cred: &CosmosCredential<M>) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+
     let address = cred.address(api)?;
 
-    let digest = sha_256(
-        &preamble_msg_arb_036(
+    let digest = cred.address_scheme().digest(
+        preamble_msg_arb_036(
             &address,
             cred.message.to_string().as_str()
         ).as_bytes()
@@ -63,6 +247,241 @@ pub fn verify_arbitrary<M : Display>(api:  &dyn Api, cred: &CosmosCredential<M>)
 
 
 
+/// Verifies a message signed with Ethereum `personal_sign` (EIP-191).
+/// Uses the public key if provided or recovers it from the signature otherwise
+/// and returns the lowercase address of the signer
+pub fn verify_eth_personal_sign<M : Display>(api:  &dyn Api, storage: &dyn Storage, cred: &EthCredential<M>) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+    let digest = keccak256(
+        &preamble_msg_eth_191(cred.message.to_string().as_bytes())
+    );
+    verify_eth_digest(api, &digest, cred)
+}
+
+
+
+/// Verifies a signature of EIP-712 typed data describing encrypted params
+/// and returns the lowercase address of the signer
+pub fn verify_eth_typed_data(api:  &dyn Api, storage: &dyn Storage, cred: &EthCredential<EncryptedTypedData>) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+    verify_eth_digest(api, &cred.message.digest(), cred)
+}
+
+
+
+fn verify_eth_digest<M : Display>(
+    api     :  &dyn Api, 
+    digest  :  &[u8], 
+    cred    :  &EthCredential<M>
+) -> StdResult<String> {
+    ensure!(
+        cred.signature.len() == 65,
+        StdError::generic_err("Ethereum signature must be 65 bytes long")
+    );
+
+    let recovery_param = match cred.signature[64] {
+        27 | 28 => cred.signature[64] - 27,
+        0 | 1 => cred.signature[64],
+        _ => return Err(StdError::generic_err("Invalid recovery parameter"))
+    };
+
+    let pubkey = match &cred.pubkey {
+        Some(pubkey) => {
+            let res = api.secp256k1_verify(digest, &cred.signature[..64], pubkey)?;
+            ensure!(res, StdError::generic_err("Signature verification failed"));
+            pubkey.to_vec()
+        },
+        None => api.secp256k1_recover_pubkey(digest, &cred.signature[..64], recovery_param)
+                .map_err(|e| StdError::generic_err(e.to_string()))?
+    };
+
+    let address = pubkey_to_eth_address(&pubkey)?;
+
+    ensure!(
+        address.eq_ignore_ascii_case(&cred.address),
+        StdError::generic_err("Signature verification failed")
+    );
+
+    Ok(address)
+}
+
+
+
+
+/// Verifies an arbitrary message (036) signed by the members of an amino multisig
+/// on behalf of the multisig address. Every provided signature must be valid
+/// and their number must reach the threshold. Returns the multisig address
+pub fn verify_multisig_arbitrary<M : Display>(api:  &dyn Api, storage: &dyn Storage, cred: &MultisigCredential<M>) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+    ensure!(
+        cred.threshold > 0 && cred.threshold as usize <= cred.pubkeys.len(),
+        StdError::generic_err("Threshold must be between 1 and the number of members")
+    );
+    ensure!(
+        cred.signatures.len() == cred.pubkeys.len(),
+        StdError::generic_err("Number of signatures must match the number of members")
+    );
+    for (i, pubkey) in cred.pubkeys.iter().enumerate() {
+        ensure!(
+            !cred.pubkeys[..i].contains(pubkey),
+            StdError::generic_err("Multisig members must be unique")
+        );
+    }
+
+    let address = cred.address(api)?;
+
+    let digest = sha_256(
+        preamble_msg_arb_036(
+            &address,
+            cred.message.to_string().as_str()
+        ).as_bytes()
+    );
+
+    let mut signed = 0;
+    for (pubkey, signature) in cred.pubkeys.iter().zip(cred.signatures.iter()) {
+        if let Some(signature) = signature {
+            let res = api.secp256k1_verify(&digest, signature, pubkey)?;
+            ensure!(res, StdError::generic_err("Signature verification failed"));
+            signed += 1;
+        }
+    }
+
+    ensure!(
+        signed >= cred.threshold,
+        StdError::generic_err(format!("Expected at least {} signatures, got {}", cred.threshold, signed))
+    );
+
+    Ok(address)
+}
+
+
+
+/// Verifies a raw message signed with an ed25519 key
+/// and returns the address of the signer
+pub fn verify_ed25519<M : Display>(api:  &dyn Api, storage: &dyn Storage, cred: &Ed25519Credential<M>) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+    let address = cred.address(api)?;
+
+    let res = api.ed25519_verify(
+        cred.message.to_string().as_bytes(),
+        &cred.signature,
+        &cred.pubkey
+    )?;
+
+    ensure!(res, StdError::generic_err("Signature verification failed"));
+
+    Ok(address)
+}
+
+
+
+#[derive(serde::Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ty          :   String,
+    challenge   :   String,
+    origin      :   String,
+}
+
+
+// relying party id (domain) that passkey assertions must be made for
+pub const PASSKEY_RP_ID         :    Item<String>   =    Item::new(b"passkey_rp_id");
+
+
+
+/// Sets the relying party id e.g. `app.example.com` that passkey assertions must be made for.
+/// Passing `None` accepts assertions made for any site
+pub fn set_passkey_rp_id(
+    storage     :   &mut dyn Storage,
+    rp_id       :   Option<String>,
+) -> StdResult<()> {
+    match rp_id {
+        Some(rp_id) => PASSKEY_RP_ID.save(storage, &rp_id),
+        None => {
+            PASSKEY_RP_ID.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Whether the origin is served over https from the relying party domain or its subdomain
+fn is_rp_origin(origin: &str, rp_id: &str) -> bool {
+    let host = match origin.strip_prefix("https://") {
+        Some(rest) => rest.split(':').next().unwrap_or_default(),
+        None => return false
+    };
+    host == rp_id || host.ends_with(&format!(".{}", rp_id))
+}
+
+
+fn passkey_verifying_key(pubkey: &[u8]) -> StdResult<VerifyingKey> {
+    VerifyingKey::from_sec1_bytes(pubkey).map_err(|e| StdError::generic_err(
+        format!("Error converting into P-256 public key {}", e)
+    ))
+}
+
+
+/// Verifies a WebAuthn assertion of a passkey. The challenge of the client data
+/// must be the base64url encoded message of the credential. If a relying party id is
+/// configured with [set_passkey_rp_id] the rpIdHash of the authenticator data and the
+/// origin of the client data must match it. Returns the address derived from the credential id
+pub fn verify_passkey(api:  &dyn Api, storage: &dyn Storage, cred: &PasskeyCredential) -> StdResult<String> {
+    ensure_not_revoked(storage, &cred.id())?;
+    let assertion = &cred.assertion;
+
+    ensure!(
+        assertion.authenticator_data.len() >= 37,
+        StdError::generic_err("Authenticator data is too short")
+    );
+    // user present flag
+    ensure!(
+        assertion.authenticator_data[32] & 0x01 == 0x01,
+        StdError::generic_err("User presence is required")
+    );
+
+    let client_data : ClientData = from_slice(&assertion.client_data_json)?;
+    ensure!(
+        client_data.ty == "webauthn.get",
+        StdError::generic_err("Client data is not an assertion")
+    );
+    ensure!(
+        client_data.challenge == to_base64url(&cred.message),
+        StdError::generic_err("Challenge doesn't match the message")
+    );
+
+    if let Some(rp_id) = PASSKEY_RP_ID.may_load(storage)? {
+        ensure!(
+            assertion.authenticator_data[..32] == sha_256(rp_id.as_bytes()),
+            StdError::generic_err("Passkey assertion is made for another relying party")
+        );
+        ensure!(
+            is_rp_origin(&client_data.origin, &rp_id),
+            StdError::generic_err("Passkey assertion is made on another origin")
+        );
+    }
+
+    let signature = match assertion.signature.len() {
+        64 => Signature::from_slice(&assertion.signature),
+        _ => Signature::from_der(&assertion.signature)
+    }.map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let signed = [
+        assertion.authenticator_data.as_slice(), 
+        &sha_256(&assertion.client_data_json)
+    ].concat();
+
+    passkey_verifying_key(&cred.pubkey)?
+        .verify(&signed, &signature)
+        .map_err(|_| StdError::generic_err("Signature verification failed"))?;
+
+    cred.address(api)
+}
+
+
+
+
 /// Decrypts a plaintext message using the ChaCha20Poly1305 algorithm.
 /// and returns the plaintext bytes.
 pub fn chacha20poly1305_decrypt(
@@ -70,22 +489,174 @@ pub fn chacha20poly1305_decrypt(
     key           :     &impl Deref<Target = [u8]>,
     nonce         :     &impl Deref<Target = [u8]>,
 ) -> StdResult<Vec<u8>> {
+    Ok(decrypt_with_cipher(&Cipher::Chacha20Poly1305, ciphertext, key, nonce, &[])?)
+}
+
+
+
+/// Encrypts a plaintext message using the ChaCha20Poly1305 algorithm.
+/// and returns the ciphertext bytes.
+pub fn chacha20poly1305_encrypt(
+    plaintext     :     &impl Deref<Target = [u8]>,
+    key           :     &impl Deref<Target = [u8]>,
+    nonce         :     &impl Deref<Target = [u8]>,
+) -> StdResult<Vec<u8>> {
+    Ok(encrypt_with_cipher(&Cipher::Chacha20Poly1305, plaintext, key, nonce, &[])?)
+}
+
+
+/// Hash of the encrypted payload that must be signed by the user. The legacy protocol hashes
+/// the ciphertext only while newer versions hash the ciphertext, the nonce and the user key,
+/// each prefixed with its length as a big endian u32
+pub fn payload_hash(
+    version       :     Option<u8>,
+    ciphertext    :     &[u8],
+    nonce         :     &[u8],
+    user_key      :     &[u8],
+) -> [u8; 32] {
+    if version.unwrap_or(PROTOCOL_V0) == PROTOCOL_V0 {
+        return sha_256(ciphertext);
+    }
+    sha_256(&[ciphertext, nonce, user_key]
+        .into_iter()
+        .flat_map(|part| [(part.len() as u32).to_be_bytes().as_slice(), part].concat())
+        .collect::<Vec<u8>>()
+    )
+}
+
+
+
+/// Derives the key for encrypting messages from the ECDH shared secret according to 
+/// the protocol version. The legacy version is assumed if not provided
+pub fn derive_encryption_key(
+    shared_secret :     &[u8],
+    version       :     Option<u8>,
+    context       :     &KeyContext,
+) -> StdResult<Vec<u8>> {
+    match version.unwrap_or(PROTOCOL_V0) {
+        PROTOCOL_V0 => Ok(shared_secret.to_vec()),
+        PROTOCOL_V1 => Ok(hkdf_sha256(shared_secret, &[], &context.info()).to_vec()),
+        version => Err(StdError::generic_err(
+            format!("Unsupported encryption protocol version {}", version)
+        ))
+    }
+}
+
+
+
+impl Cipher {
+    /// Length of the nonce required by the cipher
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            Cipher::Xchacha20Poly1305 => 24,
+            Cipher::Chacha20Poly1305 | Cipher::Aes256GcmSiv => 12,
+        }
+    }
+}
+
+
 
-    let ciper = ChaCha20Poly1305::new_from_slice(key)
-        .map_err(|e| StdError::generic_err(e.to_string()))?;
+impl AssociatedData {
+    /// Bytes authenticated by the cipher. Every part is prefixed with its length
+    pub fn to_bytes(
+        &self,
+        contract    :   &str,
+        chain_id    :   &str,
+        relayer     :   Option<&str>,
+    ) -> StdResult<Vec<u8>> {
+        let mut parts = vec![contract, chain_id];
+        if *self == AssociatedData::ContractAndRelayer {
+            parts.push(relayer.ok_or_else(|| 
+                StdError::generic_err("Relayer address is required for the associated data")
+            )?);
+        }
+        Ok(parts
+            .into_iter()
+            .flat_map(|part| [(part.len() as u16).to_be_bytes().as_slice(), part.as_bytes()].concat())
+            .collect())
+    }
+}
 
-    let nonce = Nonce::from_slice(nonce);
 
-    let plaintext = ciper.decrypt(nonce, ciphertext.as_ref())
-        .map_err(|e| StdError::generic_err(e.to_string()))?;
 
-    Ok(plaintext)
+fn aead_decrypt<C: Aead + KeyInit>(
+    ciphertext    :     &[u8],
+    key           :     &[u8],
+    nonce         :     &[u8],
+    aad           :     &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    C::new_from_slice(key)
+        .map_err(|e| StdError::generic_err(e.to_string()))?
+        .decrypt(GenericArray::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| EncryptionError::AeadFailure {})
 }
 
 
+
+fn aead_encrypt<C: Aead + KeyInit>(
+    plaintext     :     &[u8],
+    key           :     &[u8],
+    nonce         :     &[u8],
+    aad           :     &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    C::new_from_slice(key)
+        .map_err(|e| StdError::generic_err(e.to_string()))?
+        .encrypt(GenericArray::from_slice(nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| EncryptionError::AeadFailure {})
+}
+
+
+
+/// Decrypts a message with the given cipher and authenticates the associated data.
+/// Returns the plaintext bytes. Fails if the length of the nonce doesn't match the cipher
+pub fn decrypt_with_cipher(
+    cipher        :     &Cipher,
+    ciphertext    :     &impl Deref<Target = [u8]>,
+    key           :     &impl Deref<Target = [u8]>,
+    nonce         :     &impl Deref<Target = [u8]>,
+    aad           :     &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    ensure!(
+        nonce.len() == cipher.nonce_size(),
+        EncryptionError::InvalidNonceLength { expected: cipher.nonce_size(), actual: nonce.len() }
+    );
+    match cipher {
+        Cipher::Chacha20Poly1305  => aead_decrypt::<ChaCha20Poly1305>(ciphertext, key, nonce, aad),
+        Cipher::Xchacha20Poly1305 => aead_decrypt::<XChaCha20Poly1305>(ciphertext, key, nonce, aad),
+        Cipher::Aes256GcmSiv      => aead_decrypt::<Aes256GcmSiv>(ciphertext, key, nonce, aad),
+    }
+}
+
+
+
+/// Encrypts a message with the given cipher binding the associated data to it.
+/// Returns the ciphertext bytes. Fails if the length of the nonce doesn't match the cipher
+pub fn encrypt_with_cipher(
+    cipher        :     &Cipher,
+    plaintext     :     &impl Deref<Target = [u8]>,
+    key           :     &impl Deref<Target = [u8]>,
+    nonce         :     &impl Deref<Target = [u8]>,
+    aad           :     &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    ensure!(
+        nonce.len() == cipher.nonce_size(),
+        EncryptionError::InvalidNonceLength { expected: cipher.nonce_size(), actual: nonce.len() }
+    );
+    match cipher {
+        Cipher::Chacha20Poly1305  => aead_encrypt::<ChaCha20Poly1305>(plaintext, key, nonce, aad),
+        Cipher::Xchacha20Poly1305 => aead_encrypt::<XChaCha20Poly1305>(plaintext, key, nonce, aad),
+        Cipher::Aes256GcmSiv      => aead_encrypt::<Aes256GcmSiv>(plaintext, key, nonce, aad),
+    }
+}
+
+
+
 #[cfg(feature = "wallets")]
 pub mod wallets;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod client;
+
 
 #[cfg(test)]
 mod tests;
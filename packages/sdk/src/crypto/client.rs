@@ -0,0 +1,454 @@
+use std::ops::Deref;
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use secret_toolkit::crypto::sha_256;
+use cosmwasm_std::{ensure, to_vec, Binary, StdError, StdResult};
+
+use crate::{AddressScheme, AssociatedData, Cipher, CredentialKind, EncryptedParams, EncryptedPayload, EncryptedResponse, PasskeyAssertion};
+
+use super::{
+    utils::{
+        secret_key_from_bytes, public_key_from_bytes, get_common_key, 
+        preamble_msg_arb_036, preamble_msg_eth_191, keccak256, to_base64url
+    },
+    eip712::EncryptedTypedData,
+    chacha20poly1305_decrypt, pubkey_to_address_with_scheme, encrypt_with_cipher,
+    derive_encryption_key, payload_hash, KeyContext, PROTOCOL_V0
+};
+
+
+
+/// Generates a random secp256k1 secret key using the OS randomness source.
+pub fn random_secret_key() -> SecretKey {
+    loop {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        if let Ok(key) = SecretKey::from_slice(&bytes) {
+            return key;
+        }
+    }
+}
+
+
+
+/// Signs the given data wrapped in an arbitrary message (036) on behalf of the signer
+/// and returns a 64 bytes compact signature.
+pub fn sign_arbitrary(
+    signing_key   :   &SecretKey,
+    signer        :   &str,
+    data          :   &str,
+    scheme        :   &AddressScheme,
+) -> Binary {
+    let digest = scheme.digest(
+        preamble_msg_arb_036(signer, data).as_bytes()
+    );
+    let signature = Secp256k1::signing_only().sign_ecdsa(
+        &Message::from_digest(digest),
+        signing_key
+    );
+    Binary(signature.serialize_compact().to_vec())
+}
+
+
+
+/// Signs the given message with Ethereum `personal_sign` (EIP-191)
+/// and returns a 65 bytes signature with the recovery id
+pub fn sign_eth_personal(
+    signing_key   :   &SecretKey,
+    message       :   &str,
+) -> Binary {
+    sign_eth_digest(signing_key, keccak256(&preamble_msg_eth_191(message.as_bytes())))
+}
+
+
+
+/// Signs EIP-712 typed data describing encrypted params like `eth_signTypedData_v4`
+pub fn sign_eth_typed_data(
+    signing_key   :   &SecretKey,
+    typed_data    :   &EncryptedTypedData,
+) -> Binary {
+    sign_eth_digest(signing_key, typed_data.digest())
+}
+
+
+
+fn sign_eth_digest(
+    signing_key   :   &SecretKey,
+    digest        :   [u8; 32],
+) -> Binary {
+    let (recovery_id, signature) = Secp256k1::signing_only()
+        .sign_ecdsa_recoverable(&Message::from_digest(digest), signing_key)
+        .serialize_compact();
+    Binary([signature.as_slice(), &[27 + recovery_id.to_i32() as u8]].concat())
+}
+
+
+
+/// Client data JSON of a WebAuthn assertion with the given challenge
+/// as produced by browsers for `navigator.credentials.get`
+pub fn passkey_client_data(
+    challenge     :   &[u8],
+    origin        :   &str,
+) -> Binary {
+    Binary(format!(
+        "{{\"type\":\"webauthn.get\",\"challenge\":\"{}\",\"origin\":\"{}\",\"crossOrigin\":false}}",
+        to_base64url(challenge), origin
+    ).into_bytes())
+}
+
+
+
+/// Signs the challenge like a WebAuthn authenticator would. Meant for testing
+/// and for software passkeys as browsers don't expose the keys of real ones
+pub fn sign_passkey(
+    signing_key         :   &p256::ecdsa::SigningKey,
+    credential_id       :   &[u8],
+    authenticator_data  :   &[u8],
+    challenge           :   &[u8],
+    origin              :   &str,
+) -> PasskeyAssertion {
+    use p256::ecdsa::{signature::Signer, Signature};
+
+    let client_data_json = passkey_client_data(challenge, origin);
+    let signature : Signature = signing_key.sign(&[
+        authenticator_data, 
+        &sha_256(&client_data_json)
+    ].concat());
+
+    PasskeyAssertion {
+        credential_id       :   Binary(credential_id.to_vec()),
+        authenticator_data  :   Binary(authenticator_data.to_vec()),
+        client_data_json,
+        signature           :   Binary(signature.to_der().as_bytes().to_vec()),
+    }
+}
+
+
+
+/// Decrypts a response of the gateway encrypted with [crate::common::encrypt_response].
+/// Requires the builder of the request with the ephemeral key set. The key is derived
+/// according to the protocol version of the builder
+pub fn decrypt_response(
+    builder         :   &EncryptedParamsBuilder,
+    response        :   &EncryptedResponse,
+) -> StdResult<Binary> {
+    decrypt_response_with_key(&builder.response_key()?, response)
+}
+
+
+
+/// Decrypts a response of the gateway with the shared key of the request e.g. 
+/// the one returned by [EncryptedParamsBuilder::response_key]
+pub fn decrypt_response_with_key(
+    key             :   &impl Deref<Target = [u8]>,
+    response        :   &EncryptedResponse,
+) -> StdResult<Binary> {
+    Ok(Binary(chacha20poly1305_decrypt(&response.data, key, &response.nonce)?))
+}
+
+
+
+/// Decrypts an attribute value encrypted with [crate::common::EncryptionContext::encrypt_response].
+/// Requires the builder of the request with the ephemeral key set. The key is derived
+/// according to the protocol version of the builder
+pub fn decrypt_attribute(
+    builder         :   &EncryptedParamsBuilder,
+    value           :   &str,
+) -> StdResult<String> {
+    decrypt_attribute_with_key(&builder.response_key()?, value)
+}
+
+
+
+/// Decrypts an attribute value with the shared key of the request e.g. 
+/// the one returned by [EncryptedParamsBuilder::response_key]
+pub fn decrypt_attribute_with_key(
+    key             :   &impl Deref<Target = [u8]>,
+    value           :   &str,
+) -> StdResult<String> {
+    let bytes = Binary::from_base64(value)?;
+    ensure!(bytes.len() > 12, StdError::generic_err("Encrypted attribute is too short"));
+    let (nonce, data) = bytes.split_at(12);
+
+    let decrypted = decrypt_response_with_key(key, &EncryptedResponse {
+        data    :   Binary(data.to_vec()),
+        nonce   :   Binary(nonce.to_vec()),
+    })?;
+
+    String::from_utf8(decrypted.0).map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+
+
+/// Client side counterpart of [crate::crypto::wallets::SecretEncryptionWallet::decrypt_to_payload]
+/// Encrypts an [EncryptedPayload] to the public key of a gateway and signs the hash
+/// of the ciphertext to produce a complete [EncryptedParams]
+#[derive(Clone)]
+pub struct EncryptedParamsBuilder {
+    /// Public key of the gateway returned by `EncryptionKey` query
+    gateway_key     :   PublicKey,
+    /// Secret key of the user wallet used for signing the payload hash
+    signing_key     :   SecretKey,
+    /// Secret key used for deriving a shared key. Random if not provided
+    ephemeral_key   :   Option<SecretKey>,
+    /// Nonce used for encryption. Random if not provided
+    nonce           :   Option<Binary>,
+    /// Cipher used for encrypting the payload. XChaCha20-Poly1305 if not provided
+    cipher          :   Option<Cipher>,
+    /// Address and chain id of the gateway required for EIP-712 typed data
+    /// and for key derivation
+    contract        :   Option<(String, String)>,
+    /// Epoch of the gateway key. Current key is assumed if not provided
+    epoch           :   Option<u32>,
+    /// Version of the encryption protocol. Legacy one is used if not provided
+    version         :   Option<u8>,
+    /// Associated data bound to the payload. None if not provided
+    associated_data :   Option<AssociatedData>,
+    /// Address of the relayer bound with [AssociatedData::ContractAndRelayer]
+    relayer         :   Option<String>,
+}
+
+
+impl EncryptedParamsBuilder {
+
+    pub fn new(
+        gateway_key     :   &impl Deref<Target = [u8]>,
+        signing_key     :   &impl Deref<Target = [u8]>,
+    ) -> StdResult<Self> {
+        Ok(Self {
+            gateway_key     :   public_key_from_bytes(gateway_key)?,
+            signing_key     :   secret_key_from_bytes(signing_key)?,
+            ephemeral_key   :   None,
+            nonce           :   None,
+            cipher          :   None,
+            contract        :   None,
+            epoch           :   None,
+            version         :   None,
+            associated_data :   None,
+            relayer         :   None,
+        })
+    }
+
+    pub fn ephemeral_key(
+        mut self,
+        ephemeral_key   :   &impl Deref<Target = [u8]>
+    ) -> StdResult<Self> {
+        self.ephemeral_key = Some(secret_key_from_bytes(ephemeral_key)?);
+        Ok(self)
+    }
+
+    pub fn nonce(
+        mut self,
+        nonce           :   &impl Deref<Target = [u8]>
+    ) -> StdResult<Self> {
+        ensure!(
+            nonce.len() == 12 || nonce.len() == 24, 
+            StdError::generic_err("Nonce must be 12 or 24 bytes long")
+        );
+        self.nonce = Some(Binary(nonce.to_vec()));
+        Ok(self)
+    }
+
+    pub fn cipher(
+        mut self,
+        cipher              :   Cipher,
+    ) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    pub fn contract(
+        mut self,
+        contract_address    :   &str,
+        chain_id            :   &str,
+    ) -> Self {
+        self.contract = Some((contract_address.to_string(), chain_id.to_string()));
+        self
+    }
+
+    pub fn epoch(
+        mut self,
+        epoch               :   u32,
+    ) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Version of the encryption protocol. Versions other than the legacy one 
+    /// require the contract address, the chain id and the epoch to be set
+    pub fn version(
+        mut self,
+        version             :   u8,
+    ) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Binds the address and the chain id of the gateway to the payload as associated data.
+    /// Requires the contract to be set
+    pub fn bind_contract(
+        mut self,
+    ) -> Self {
+        self.associated_data = Some(AssociatedData::Contract);
+        self
+    }
+
+    /// Same as [EncryptedParamsBuilder::bind_contract] but also binds the address of
+    /// the relayer so that the message can't be submitted by anyone else
+    pub fn bind_relayer(
+        mut self,
+        relayer             :   &str,
+    ) -> Self {
+        self.associated_data = Some(AssociatedData::ContractAndRelayer);
+        self.relayer = Some(relayer.to_string());
+        self
+    }
+
+    fn aad(&self) -> StdResult<Vec<u8>> {
+        match &self.associated_data {
+            Some(associated) => {
+                let (contract, chain_id) = self.contract.as_ref().ok_or_else(||
+                    StdError::generic_err("Contract address and chain id are required for associated data")
+                )?;
+                associated.to_bytes(contract, chain_id, self.relayer.as_deref())
+            },
+            None => Ok(vec![])
+        }
+    }
+
+    /// Key shared with the gateway that must be used for decrypting its responses.
+    /// Requires the ephemeral key to be set
+    pub fn response_key(&self) -> StdResult<Vec<u8>> {
+        let ephemeral_key = self.ephemeral_key.ok_or_else(||
+            StdError::generic_err("Ephemeral key must be set for deriving the response key")
+        )?;
+        self.encryption_key(ephemeral_key)
+    }
+
+    fn encryption_key(
+        &self,
+        ephemeral_key       :   SecretKey,
+    ) -> StdResult<Vec<u8>> {
+        let context = if self.version.unwrap_or(PROTOCOL_V0) == PROTOCOL_V0 {
+            KeyContext::default()
+        } else {
+            let (contract, chain_id) = self.contract.clone().ok_or_else(||
+                StdError::generic_err("Contract address and chain id are required for key derivation")
+            )?;
+            let epoch = self.epoch.ok_or_else(||
+                StdError::generic_err("Epoch of the gateway key is required for key derivation")
+            )?;
+            KeyContext { contract, chain_id, epoch }
+        };
+
+        derive_encryption_key(
+            &get_common_key(self.gateway_key, ephemeral_key), 
+            self.version, 
+            &context
+        )
+    }
+
+    /// Compressed public key of the signing wallet
+    pub fn signing_pubkey(&self) -> Binary {
+        Binary(self.signing_key.public_key(&Secp256k1::signing_only()).serialize().to_vec())
+    }
+
+    /// Encrypts the payload without signing it. The `payload_signature` of the returned
+    /// params is empty and must be filled in by the caller e.g. with a JSON encoded
+    /// [PasskeyAssertion] made for the `payload_hash` challenge or an ed25519 signature
+    pub fn encrypt(
+        &self,
+        payload     :   &EncryptedPayload
+    ) -> StdResult<EncryptedParams> {
+        let secp = Secp256k1::signing_only();
+
+        let ephemeral_key = self.ephemeral_key.unwrap_or_else(random_secret_key);
+
+        let cipher = self.cipher.clone().unwrap_or(Cipher::Xchacha20Poly1305);
+
+        let nonce = match &self.nonce {
+            Some(nonce) => nonce.clone(),
+            None => {
+                let mut nonce = vec![0u8; cipher.nonce_size()];
+                OsRng.fill_bytes(&mut nonce);
+                Binary(nonce)
+            }
+        };
+
+        let key = self.encryption_key(ephemeral_key)?;
+
+        let ciphertext = encrypt_with_cipher(
+            &cipher,
+            &to_vec(payload)?,
+            &key,
+            &nonce,
+            &self.aad()?
+        )?;
+
+        let user_key = Binary(PublicKey::from_secret_key(&secp, &ephemeral_key).serialize().to_vec());
+
+        let payload_hash = Binary(payload_hash(self.version, &ciphertext, &nonce, &user_key).to_vec());
+
+        Ok(EncryptedParams {
+            payload             :   Binary(ciphertext),
+            payload_hash,
+            payload_signature   :   Binary::default(),
+            user_key,
+            nonce,
+            epoch               :   self.epoch,
+            version             :   self.version,
+            cipher              :   Some(cipher),
+            associated_data     :   self.associated_data.clone(),
+        })
+    }
+
+    pub fn build(
+        &self,
+        payload     :   &EncryptedPayload
+    ) -> StdResult<EncryptedParams> {
+        let signing_pubkey = self.signing_pubkey();
+        let kind = payload.credential_kind();
+        ensure!(
+            kind != CredentialKind::Passkey && kind != CredentialKind::Ed25519,
+            StdError::generic_err("Only secp256k1 credentials can be signed by the builder. Use `encrypt` instead")
+        );
+        ensure!(
+            payload.user_pubkey == signing_pubkey || 
+            (kind != CredentialKind::CosmosArbitrary && payload.user_pubkey.is_empty()),
+            StdError::generic_err("Payload public key doesn't match the signing key")
+        );
+
+        let mut params = self.encrypt(payload)?;
+        let payload_hash = &params.payload_hash;
+
+        params.payload_signature = match kind {
+            CredentialKind::CosmosArbitrary => {
+                let scheme = payload.scheme.clone().unwrap_or_else(|| AddressScheme::from_hrp(&payload.hrp));
+                let address = pubkey_to_address_with_scheme(&signing_pubkey, &payload.hrp, &scheme)?;
+                ensure!(
+                    payload.declares_address(&address),
+                    StdError::generic_err("Payload address doesn't match the signing key")
+                );
+                sign_arbitrary(&self.signing_key, &address, &payload_hash.to_base64(), &scheme)
+            },
+            CredentialKind::EthPersonalSign => sign_eth_personal(
+                &self.signing_key, 
+                &payload_hash.to_base64()
+            ),
+            CredentialKind::EthTypedData => {
+                let (contract_address, chain_id) = self.contract.clone().ok_or_else(||
+                    StdError::generic_err("Contract address and chain id are required for typed data")
+                )?;
+                sign_eth_typed_data(&self.signing_key, &EncryptedTypedData {
+                    payload_hash        :   payload_hash.clone(),
+                    nonce               :   params.nonce.clone(),
+                    contract_address,
+                    chain_id,
+                })
+            },
+            CredentialKind::Passkey | CredentialKind::Ed25519 => unreachable!()
+        };
+
+        Ok(params)
+    }
+}
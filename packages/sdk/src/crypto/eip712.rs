@@ -0,0 +1,80 @@
+use std::fmt::Display;
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Binary, Env};
+
+use crate::EncryptedParams;
+use super::utils::keccak256;
+
+
+/// Name of the EIP-712 domain of the gateway
+pub const EIP712_DOMAIN_NAME    : &str = "Secret Gateway";
+/// Version of the EIP-712 domain of the gateway
+pub const EIP712_DOMAIN_VERSION : &str = "1";
+
+/// EIP-712 type of the domain
+pub const EIP712_DOMAIN_TYPE    : &str = "EIP712Domain(string name,string version)";
+/// EIP-712 type of the signed encrypted params
+pub const ENCRYPTED_PARAMS_TYPE : &str = "EncryptedParams(bytes payloadHash,bytes nonce,string contractAddress,string chainId)";
+
+
+
+/// Typed data describing encrypted params that is signed with `eth_signTypedData_v4`.
+/// Binds the signature to the gateway contract and the chain it is deployed on
+#[cw_serde]
+pub struct EncryptedTypedData {
+    /// Sha256 hash of the encrypted payload
+    pub payload_hash        :   Binary,
+    /// Nonce used for the encryption of the payload
+    pub nonce               :   Binary,
+    /// Address of the gateway contract
+    pub contract_address    :   String,
+    /// Chain id of the network the gateway is deployed on
+    pub chain_id            :   String,
+}
+
+
+impl EncryptedTypedData {
+
+    pub fn new(params: &EncryptedParams, env: &Env) -> Self {
+        Self {
+            payload_hash        :   params.payload_hash.clone(),
+            nonce               :   params.nonce.clone(),
+            contract_address    :   env.contract.address.to_string(),
+            chain_id            :   env.block.chain_id.clone(),
+        }
+    }
+
+    pub fn domain_separator() -> [u8; 32] {
+        keccak256(&[
+            keccak256(EIP712_DOMAIN_TYPE.as_bytes()),
+            keccak256(EIP712_DOMAIN_NAME.as_bytes()),
+            keccak256(EIP712_DOMAIN_VERSION.as_bytes()),
+        ].concat())
+    }
+
+    pub fn struct_hash(&self) -> [u8; 32] {
+        keccak256(&[
+            keccak256(ENCRYPTED_PARAMS_TYPE.as_bytes()),
+            keccak256(&self.payload_hash),
+            keccak256(&self.nonce),
+            keccak256(self.contract_address.as_bytes()),
+            keccak256(self.chain_id.as_bytes()),
+        ].concat())
+    }
+
+    /// Digest signed by the wallet
+    pub fn digest(&self) -> [u8; 32] {
+        keccak256(&[
+            b"\x19\x01".as_slice(),
+            &Self::domain_separator(),
+            &self.struct_hash(),
+        ].concat())
+    }
+}
+
+
+impl Display for EncryptedTypedData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.digest()))
+    }
+}
@@ -1,11 +1,14 @@
 use cosmwasm_std::{Binary, testing::mock_dependencies};
 use super::{*, utils::{get_common_key, secret_key_from_bytes, public_key_from_bytes}};
+use crate::test_utils::{
+    encrypted_test_builder, encrypted_test_payload, encrypted_test_setup, 
+    CONTRACT_PRIVATE, CONTRACT_PUBLIC, ED25519_PRIVATE, ETH_ADDRESS, ETH_PRIVATE, SIGN_HRP, USER_PRIVATE
+};
 
 const SIGNER : &str = "cosmos1pkptre7fdkl6gfrzlesjjvhxhlc3r4gmmk8rs6";
 const SIGNED_MSG : &str = "SGVsbG8sIHdvcmxk";
 const SIGNATURE : &str = "x9jjSFv8/n1F8gOSRjddakYDbvroQm8ZoDWht/Imc1t5xUW49+Xaq7gwcsE+LCpqYoTBxnaXLg/xgJjYymCWvw==";
 const SIGNING_PUBKEY : &str = "A08EGB7ro1ORuFhjOnZcSgwYlpe0DSFjVNUIkNNQxwKQ";
-const SIGN_HRP : &str = "cosmos";
 
 
 
@@ -41,19 +44,21 @@ fn test_036_verification() {
         signature: Binary::from_base64(SIGNATURE).unwrap(),
         message: SIGNED_MSG,
         pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
-        hrp: SIGN_HRP.to_string()
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
     };
 
-    assert!(verify_arbitrary(&deps.api, &cred).is_ok());
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &cred).is_ok());
 
     // wrong signature
     let cred2 = CosmosCredential {
         signature: Binary::from_base64("d3Jvbmc=").unwrap(),
         message: Binary::from_base64(SIGNED_MSG).unwrap(),
         pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
-        hrp: SIGN_HRP.to_string()
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
     };
-    assert!(verify_arbitrary(&deps.api, &cred2).is_err());
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &cred2).is_err());
 
 
     // wrong message
@@ -61,33 +66,34 @@ fn test_036_verification() {
         signature: Binary::from_base64(SIGNATURE).unwrap(),
         message: Binary::from_base64("d3Jvbmc=").unwrap(),
         pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
-        hrp: SIGN_HRP.to_string()
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
     };
-    assert!(verify_arbitrary(&deps.api, &cred3).is_err());
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &cred3).is_err());
 
     // wrong pubkey
     let cred4 = CosmosCredential {
         signature: Binary::from_base64(SIGNATURE).unwrap(),
         message: Binary::from_base64(SIGNED_MSG).unwrap(),
         pubkey: Binary::from_base64("d3Jvbmc=").unwrap(),
-        hrp: SIGN_HRP.to_string()
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
     };
-    assert!(verify_arbitrary(&deps.api, &cred4).is_err());
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &cred4).is_err());
 
     // different hrp
     let cred5 = CosmosCredential {
         signature: Binary::from_base64(SIGNATURE).unwrap(),
         message: Binary::from_base64(SIGNED_MSG).unwrap(),
         pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
-        hrp: "secret".to_string()
+        hrp: "secret".to_string(),
+        scheme: None
     };
-    assert!(verify_arbitrary(&deps.api, &cred5).is_err());
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &cred5).is_err());
 }
 
 
 
-const CONTRACT_PRIVATE : &str = "OIxm5RnQkzCDMMoXv9LIkmVhrr2+AfFfneOsmXfSwQ0=";
-const _CONTRACT_PUBLIC  : &str = "A5OJoIXQRceFbnqUe2rJ3s2MKAFEiPrKZ86eHPyZvSl5";
 const CLIENT_PUBLIC    : &str = "AgGQoJ1UiOfUW1PKCAnoYS+JM9efvuIUTjjmFO7/Y+MZ";
 
 const COMMON_KEY       : &str = "vkgtL7d53z12+Ies8iKnhG2HkvEBMCLmrQFwoLqccOo=";
@@ -130,4 +136,1206 @@ fn can_decrypt() {
     ).unwrap();
 
     assert_eq!(Binary(decrypted).to_base64(), PLAINTEXT);
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn can_encrypt() {
+    let encrypted = chacha20poly1305_encrypt(
+        &Binary::from_base64(PLAINTEXT).unwrap(),
+        &Binary::from_base64(COMMON_KEY).unwrap(),
+        &Binary::from_base64(NONCE).unwrap(),
+    ).unwrap();
+
+    assert_eq!(Binary(encrypted).to_base64(), CIPHERTEXT);
+}
+
+
+
+#[test]
+fn client_encryption_round_trip() {
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+
+    let builder = encrypted_test_builder();
+
+    let payload = encrypted_test_payload(&builder, &inner);
+    let params = builder.build(&payload).unwrap();
+
+    let (msg, info) = handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+    ).unwrap();
+
+    assert_eq!(msg, inner);
+    assert_eq!(info.sender.as_str(), payload.user_address);
+
+    // same nonce can't be used twice
+    assert!(handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params)
+    ).is_err());
+}
+
+
+#[test]
+fn client_encryption_with_fixed_keys() {
+    let builder = client::EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &USER_PRIVATE.to_vec()
+    ).unwrap()
+    .ephemeral_key(&Binary::from_base64(CONTRACT_PRIVATE).unwrap()).unwrap()
+    .nonce(&Binary::from_base64(NONCE).unwrap()).unwrap()
+    .cipher(crate::Cipher::Chacha20Poly1305);
+
+    let payload = encrypted_test_payload(
+        &builder, 
+        &crate::gateway::GatewayExecuteMsg::ResetEncryptionKey {}
+    );
+
+    // deterministic when both the ephemeral key and the nonce are fixed
+    assert_eq!(builder.build(&payload).unwrap(), builder.build(&payload).unwrap());
+
+    // wrong nonce size
+    assert!(builder.nonce(&vec![0u8; 8]).is_err());
+
+    // payload signed by another key
+    let other = client::EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &[9u8; 32].to_vec()
+    ).unwrap();
+    assert!(other.build(&payload).is_err());
+}
+
+
+
+const ETH_MESSAGE   : &str = "Some data";
+const ETH_SIGNATURE : &str = "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+
+#[test]
+fn test_eth_personal_sign() {
+    let deps = mock_dependencies();
+    let signing_key = utils::secret_key_from_bytes(&hex::decode(ETH_PRIVATE).unwrap()).unwrap();
+    let pubkey = signing_key.public_key(&secp256k1::Secp256k1::signing_only()).serialize();
+
+    assert_eq!(pubkey_to_eth_address(&pubkey).unwrap(), ETH_ADDRESS.to_lowercase());
+
+    let cred = crate::EthCredential {
+        address: ETH_ADDRESS.to_string(),
+        signature: Binary(hex::decode(ETH_SIGNATURE).unwrap()),
+        message: ETH_MESSAGE,
+        pubkey: None,
+    };
+    // recovered from the signature
+    assert_eq!(verify_eth_personal_sign(&deps.api, &deps.storage, &cred).unwrap(), ETH_ADDRESS.to_lowercase());
+
+    // with a public key
+    let with_pubkey = crate::EthCredential { pubkey: Some(Binary(pubkey.to_vec())), ..cred.clone() };
+    assert!(verify_eth_personal_sign(&deps.api, &deps.storage, &with_pubkey).is_ok());
+
+    // same as produced by the client
+    assert_eq!(client::sign_eth_personal(&signing_key, ETH_MESSAGE), cred.signature);
+
+    // wrong message
+    let wrong_message = crate::EthCredential { message: "Other data", ..cred.clone() };
+    assert!(verify_eth_personal_sign(&deps.api, &deps.storage, &wrong_message).is_err());
+
+    // wrong address
+    let wrong_address = crate::EthCredential { address: "0x0000000000000000000000000000000000000000".to_string(), ..cred.clone() };
+    assert!(verify_eth_personal_sign(&deps.api, &deps.storage, &wrong_address).is_err());
+
+    // public key of another signer
+    let wrong_pubkey = crate::EthCredential { pubkey: Some(Binary::from_base64(SIGNING_PUBKEY).unwrap()), ..cred.clone() };
+    assert!(verify_eth_personal_sign(&deps.api, &deps.storage, &wrong_pubkey).is_err());
+
+    // mixed with cosmos credentials
+    let auth_data = crate::CosmosAuthData {
+        credentials: vec![
+            crate::EthCredential { 
+                address: cred.address.clone(),
+                signature: cred.signature.clone(),
+                message: ETH_MESSAGE.to_string(),
+                pubkey: None,
+            }.into(),
+            CosmosCredential {
+                signature: Binary::from_base64(SIGNATURE).unwrap(),
+                message: SIGNED_MSG.to_string(),
+                pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
+                hrp: SIGN_HRP.to_string(),
+                scheme: None
+            }.into()
+        ],
+        primary_index: None,
+        threshold: None,
+    };
+    assert!(auth_data.verify(&deps.api, &deps.storage).is_ok());
+    assert_eq!(auth_data.primary_address(&deps.api).unwrap(), ETH_ADDRESS.to_lowercase());
+    assert_eq!(auth_data.secondary_addresses(&deps.api).unwrap(), vec![SIGNER.to_string()]);
+}
+
+
+#[test]
+fn eth_encryption_round_trip() {
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = client::EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &hex::decode(ETH_PRIVATE).unwrap()
+    ).unwrap();
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = crate::EncryptedPayload {
+        user_address: ETH_ADDRESS.to_string(),
+        user_pubkey: Binary::default(),
+        hrp: "eth".to_string(),
+        msg: cosmwasm_std::to_binary(&inner).unwrap(),
+        kind: None,
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    };
+
+    let (msg, info) = handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(builder.build(&payload).unwrap())
+    ).unwrap();
+
+    assert_eq!(msg, inner);
+    assert_eq!(info.sender.as_str(), ETH_ADDRESS.to_lowercase());
+}
+
+
+
+#[test]
+fn eth_typed_data_round_trip() {
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg, CredentialKind};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = client::EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &hex::decode(ETH_PRIVATE).unwrap()
+    ).unwrap();
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = crate::EncryptedPayload {
+        user_address: ETH_ADDRESS.to_string(),
+        user_pubkey: Binary::default(),
+        hrp: "eth".to_string(),
+        msg: cosmwasm_std::to_binary(&inner).unwrap(),
+        kind: Some(CredentialKind::EthTypedData),
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    };
+
+    // contract and chain are required
+    assert!(builder.build(&payload).is_err());
+
+    let mut wrapper = |builder: &client::EncryptedParamsBuilder| handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &env,
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(builder.build(&payload).unwrap())
+    );
+
+    // signed for another chain
+    let other_chain = builder.clone().contract(env.contract.address.as_str(), "secret-4");
+    assert!(wrapper(&other_chain).is_err());
+
+    // signed for another contract
+    let other_contract = builder.clone().contract("secret1other", &env.block.chain_id);
+    assert!(wrapper(&other_contract).is_err());
+
+    let correct = builder.contract(env.contract.address.as_str(), &env.block.chain_id);
+    let (msg, info) = wrapper(&correct).unwrap();
+    assert_eq!(msg, inner);
+    assert_eq!(info.sender.as_str(), ETH_ADDRESS.to_lowercase());
+}
+
+
+
+#[test]
+fn test_eth_secp256k1_addresses() {
+    let deps = mock_dependencies();
+    let signing_key = utils::secret_key_from_bytes(&hex::decode(ETH_PRIVATE).unwrap()).unwrap();
+    let pubkey = Binary(signing_key.public_key(&secp256k1::Secp256k1::signing_only()).serialize().to_vec());
+
+    // same bytes as the ethereum address
+    let eth_bytes = hex::decode(&ETH_ADDRESS[2..]).unwrap();
+    let inj_address = bech32::encode::<bech32::Bech32>(bech32::Hrp::parse("inj").unwrap(), &eth_bytes).unwrap();
+
+    assert_eq!(pubkey_to_address(&pubkey, "inj").unwrap(), inj_address);
+    assert!(pubkey_to_address(&pubkey, "evmos").unwrap().starts_with("evmos1"));
+    assert_ne!(
+        pubkey_to_address_with_scheme(&pubkey, "inj", &crate::AddressScheme::Cosmos).unwrap(),
+        inj_address
+    );
+
+    // signed with keccak256 digest by the wallets of ethsecp256k1 chains
+    let cred = CosmosCredential {
+        signature: client::sign_arbitrary(&signing_key, &inj_address, SIGNED_MSG, &crate::AddressScheme::EthSecp256k1),
+        message: SIGNED_MSG,
+        pubkey: pubkey.clone(),
+        hrp: "inj".to_string(),
+        scheme: None
+    };
+    assert_eq!(verify_arbitrary(&deps.api, &deps.storage, &cred).unwrap(), inj_address);
+
+    // scheme can be selected explicitly for other prefixes
+    let dym_address = bech32::encode::<bech32::Bech32>(bech32::Hrp::parse("dym").unwrap(), &eth_bytes).unwrap();
+    let explicit = CosmosCredential {
+        signature: client::sign_arbitrary(&signing_key, &dym_address, SIGNED_MSG, &crate::AddressScheme::EthSecp256k1),
+        hrp: "dym".to_string(),
+        scheme: Some(crate::AddressScheme::EthSecp256k1),
+        ..cred.clone()
+    };
+    assert_eq!(verify_arbitrary(&deps.api, &deps.storage, &explicit).unwrap(), dym_address);
+
+    // wrong scheme
+    let wrong = CosmosCredential { scheme: Some(crate::AddressScheme::Cosmos), ..cred };
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &wrong).is_err());
+}
+
+
+
+const PASSKEY_PRIVATE : [u8; 32] = [9u8; 32];
+const PASSKEY_ID      : &[u8]    = b"passkey-credential-id";
+const PASSKEY_ORIGIN  : &str     = "https://app.example.com";
+
+
+fn passkey_authenticator_data(flags: u8) -> Vec<u8> {
+    [sha_256(b"app.example.com").as_slice(), &[flags], &[0, 0, 0, 1]].concat()
+}
+
+
+#[test]
+fn test_passkey_verification() {
+    let deps = mock_dependencies();
+    let signing_key = p256::ecdsa::SigningKey::from_slice(&PASSKEY_PRIVATE).unwrap();
+    let pubkey = Binary(signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec());
+    let challenge = Binary::from_base64(SIGNED_MSG).unwrap();
+
+    let cred = crate::PasskeyCredential {
+        pubkey: pubkey.clone(),
+        assertion: client::sign_passkey(
+            &signing_key, PASSKEY_ID, &passkey_authenticator_data(0x05), &challenge, PASSKEY_ORIGIN
+        ),
+        message: challenge.clone(),
+        hrp: SIGN_HRP.to_string(),
+    };
+
+    let address = verify_passkey(&deps.api, &deps.storage, &cred).unwrap();
+    assert!(address.starts_with("cosmos1"));
+
+    // stable for both encodings of the public key
+    let compressed = Binary(signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec());
+    assert_eq!(pubkey_to_passkey_address(PASSKEY_ID, &compressed, SIGN_HRP).unwrap(), address);
+    // but differs for another credential
+    assert_ne!(pubkey_to_passkey_address(b"other-id", &pubkey, SIGN_HRP).unwrap(), address);
+
+    // raw (r, s) signatures are accepted too
+    let mut raw = cred.clone();
+    raw.assertion.signature = Binary(
+        p256::ecdsa::Signature::from_der(&cred.assertion.signature).unwrap().to_bytes().to_vec()
+    );
+    assert_eq!(verify_passkey(&deps.api, &deps.storage, &raw).unwrap(), address);
+
+    // challenge of another message
+    let other_message = crate::PasskeyCredential { message: Binary(b"other".to_vec()), ..cred.clone() };
+    assert!(verify_passkey(&deps.api, &deps.storage, &other_message).is_err());
+
+    // signed by another key
+    let other_key = p256::ecdsa::SigningKey::from_slice(&[10u8; 32]).unwrap();
+    let other_signer = crate::PasskeyCredential {
+        assertion: client::sign_passkey(
+            &other_key, PASSKEY_ID, &passkey_authenticator_data(0x05), &challenge, PASSKEY_ORIGIN
+        ),
+        ..cred.clone()
+    };
+    assert!(verify_passkey(&deps.api, &deps.storage, &other_signer).is_err());
+
+    // user wasn't present
+    let not_present = crate::PasskeyCredential {
+        assertion: client::sign_passkey(
+            &signing_key, PASSKEY_ID, &passkey_authenticator_data(0x04), &challenge, PASSKEY_ORIGIN
+        ),
+        ..cred.clone()
+    };
+    assert!(verify_passkey(&deps.api, &deps.storage, &not_present).is_err());
+
+    // tampered authenticator data
+    let mut tampered = cred.clone();
+    tampered.assertion.authenticator_data.0[36] = 2;
+    assert!(verify_passkey(&deps.api, &deps.storage, &tampered).is_err());
+
+    // works inside of auth data
+    let auth = crate::CosmosAuthData {
+        credentials: vec![crate::Credential::Passkey(cred)],
+        primary_index: None,
+        threshold: None,
+    };
+    auth.verify(&deps.api, &deps.storage).unwrap();
+    assert_eq!(auth.primary_address(&deps.api).unwrap(), address);
+}
+
+
+
+#[test]
+fn passkey_relying_party() {
+    let mut deps = mock_dependencies();
+    let signing_key = p256::ecdsa::SigningKey::from_slice(&PASSKEY_PRIVATE).unwrap();
+    let challenge = Binary::from_base64(SIGNED_MSG).unwrap();
+    let credential = |authenticator_data: &[u8], origin: &str| crate::PasskeyCredential {
+        pubkey: Binary(signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec()),
+        assertion: client::sign_passkey(&signing_key, PASSKEY_ID, authenticator_data, &challenge, origin),
+        message: challenge.clone(),
+        hrp: SIGN_HRP.to_string(),
+    };
+    let cred = credential(&passkey_authenticator_data(0x05), PASSKEY_ORIGIN);
+
+    set_passkey_rp_id(&mut deps.storage, Some("app.example.com".to_string())).unwrap();
+    assert!(verify_passkey(&deps.api, &deps.storage, &cred).is_ok());
+
+    // assertion made for another site
+    set_passkey_rp_id(&mut deps.storage, Some("other.com".to_string())).unwrap();
+    assert!(verify_passkey(&deps.api, &deps.storage, &cred).is_err());
+
+    // the right rpIdHash on another origin
+    let other_rp = [sha_256(b"other.com").as_slice(), &[0x05], &[0, 0, 0, 1]].concat();
+    assert!(verify_passkey(&deps.api, &deps.storage, &credential(&other_rp, "https://evil.com")).is_err());
+    assert!(verify_passkey(&deps.api, &deps.storage, &credential(&other_rp, "http://other.com")).is_err());
+    assert!(verify_passkey(&deps.api, &deps.storage, &credential(&other_rp, "https://other.com.evil.com")).is_err());
+    assert!(verify_passkey(&deps.api, &deps.storage, &credential(&other_rp, "https://login.other.com:8443")).is_ok());
+
+    // any site without the relying party
+    set_passkey_rp_id(&mut deps.storage, None).unwrap();
+    assert!(verify_passkey(&deps.api, &deps.storage, &cred).is_ok());
+}
+
+
+
+#[test]
+fn passkey_encryption_round_trip() {
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg, CredentialKind};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let signing_key = p256::ecdsa::SigningKey::from_slice(&PASSKEY_PRIVATE).unwrap();
+    let pubkey = Binary(signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec());
+    let address = pubkey_to_passkey_address(PASSKEY_ID, &pubkey, "secret").unwrap();
+
+    // the secp256k1 key is only used by the builder for the ecdh
+    let builder = encrypted_test_builder();
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = crate::EncryptedPayload {
+        user_address: address.clone(),
+        user_pubkey: pubkey,
+        hrp: "secret".to_string(),
+        msg: cosmwasm_std::to_binary(&inner).unwrap(),
+        kind: Some(CredentialKind::Passkey),
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    };
+
+    // can't be signed by the builder
+    assert!(builder.build(&payload).is_err());
+
+    let mut params = builder.encrypt(&payload).unwrap();
+    let mut wrapper = |params: &crate::EncryptedParams| handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+    );
+
+    // challenge isn't the payload hash
+    params.payload_signature = cosmwasm_std::to_binary(&client::sign_passkey(
+        &signing_key, PASSKEY_ID, &passkey_authenticator_data(0x05), &params.nonce, PASSKEY_ORIGIN
+    )).unwrap();
+    assert!(wrapper(&params).is_err());
+
+    params.payload_signature = cosmwasm_std::to_binary(&client::sign_passkey(
+        &signing_key, PASSKEY_ID, &passkey_authenticator_data(0x05), &params.payload_hash, PASSKEY_ORIGIN
+    )).unwrap();
+    let (msg, info) = wrapper(&params).unwrap();
+    assert_eq!(msg, inner);
+    assert_eq!(info.sender.as_str(), address);
+}
+
+
+
+
+
+#[test]
+fn test_ed25519_verification() {
+    use ed25519_dalek::Signer;
+    use crate::{Ed25519AddressFormat, Ed25519Credential};
+
+    let deps = mock_dependencies();
+
+    // solana system program
+    assert_eq!(
+        pubkey_to_ed25519_address(&[0u8; 32], &Ed25519AddressFormat::Base58).unwrap(),
+        "11111111111111111111111111111111"
+    );
+    assert!(pubkey_to_ed25519_address(&[0u8; 33], &Ed25519AddressFormat::Base58).is_err());
+
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&ED25519_PRIVATE);
+    let pubkey = Binary(signing_key.verifying_key().to_bytes().to_vec());
+
+    let cred = Ed25519Credential {
+        pubkey: pubkey.clone(),
+        signature: Binary(signing_key.sign(SIGNED_MSG.as_bytes()).to_bytes().to_vec()),
+        message: SIGNED_MSG.to_string(),
+        format: None,
+    };
+    assert_eq!(
+        verify_ed25519(&deps.api, &deps.storage, &cred).unwrap(), 
+        bs58::encode(pubkey.as_slice()).into_string()
+    );
+
+    let near = Ed25519Credential { format: Some(Ed25519AddressFormat::Hex), ..cred.clone() };
+    let near_address = verify_ed25519(&deps.api, &deps.storage, &near).unwrap();
+    assert_eq!(near_address, hex::encode(pubkey.as_slice()));
+    assert_eq!(Ed25519AddressFormat::from_address(&near_address), Ed25519AddressFormat::Hex);
+
+    let wrong_message = Ed25519Credential { message: "other".to_string(), ..cred.clone() };
+    assert!(verify_ed25519(&deps.api, &deps.storage, &wrong_message).is_err());
+
+    let auth = crate::CosmosAuthData {
+        credentials: vec![cred.into()],
+        primary_index: None,
+        threshold: None,
+    };
+    auth.verify(&deps.api, &deps.storage).unwrap();
+    assert_eq!(auth.primary_id(), pubkey.0);
+}
+
+
+
+#[test]
+fn ed25519_encryption_round_trip() {
+    use ed25519_dalek::Signer;
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg, CredentialKind};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&ED25519_PRIVATE);
+    let pubkey = Binary(signing_key.verifying_key().to_bytes().to_vec());
+    let address = bs58::encode(pubkey.as_slice()).into_string();
+
+    let builder = encrypted_test_builder();
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = crate::EncryptedPayload {
+        user_address: address.clone(),
+        user_pubkey: pubkey,
+        hrp: "solana".to_string(),
+        msg: cosmwasm_std::to_binary(&inner).unwrap(),
+        kind: Some(CredentialKind::Ed25519),
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    };
+
+    assert!(builder.build(&payload).is_err());
+
+    let mut params = builder.encrypt(&payload).unwrap();
+    params.payload_signature = Binary(
+        signing_key.sign(params.payload_hash.to_base64().as_bytes()).to_bytes().to_vec()
+    );
+
+    let (msg, info) = handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params)
+    ).unwrap();
+
+    assert_eq!(msg, inner);
+    assert_eq!(info.sender.as_str(), address);
+}
+
+
+
+#[test]
+fn threshold_auth_data() {
+    use ed25519_dalek::Signer;
+    use crate::{CosmosAuthData, Credential, Ed25519Credential};
+
+    let deps = mock_dependencies();
+
+    let credential = |seed: u8, signed: bool| -> Credential {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        let signature = if signed {
+            Binary(signing_key.sign(SIGNED_MSG.as_bytes()).to_bytes().to_vec())
+        } else {
+            Binary(vec![0u8; 64])
+        };
+        Ed25519Credential {
+            pubkey: Binary(signing_key.verifying_key().to_bytes().to_vec()),
+            signature,
+            message: SIGNED_MSG.to_string(),
+            format: None,
+        }.into()
+    };
+
+    let auth = CosmosAuthData {
+        credentials: vec![credential(1, true), credential(2, false), credential(3, true)],
+        primary_index: None,
+        threshold: Some(2),
+    };
+    auth.verify(&deps.api, &deps.storage).unwrap();
+
+    // all credentials must be valid without a threshold
+    assert!(CosmosAuthData { threshold: None, ..auth.clone() }.verify(&deps.api, &deps.storage).is_err());
+
+    // not enough signatures
+    let one_signed = CosmosAuthData {
+        credentials: vec![credential(1, true), credential(2, false), credential(3, false)],
+        ..auth.clone()
+    };
+    assert!(one_signed.verify(&deps.api, &deps.storage).is_err());
+
+    // identity of the key set doesn't depend on the order or on who signed
+    let address = auth.primary_address(&deps.api).unwrap();
+    assert!(address.starts_with("secret1"));
+    let reordered = CosmosAuthData {
+        credentials: vec![credential(3, false), credential(1, true), credential(2, true)],
+        ..auth.clone()
+    };
+    reordered.verify(&deps.api, &deps.storage).unwrap();
+    assert_eq!(reordered.primary_address(&deps.api).unwrap(), address);
+    assert_eq!(reordered.primary_id(), auth.primary_id());
+
+    // but depends on the threshold and on the set
+    let all_of = CosmosAuthData { threshold: Some(3), ..reordered.clone() };
+    assert_ne!(all_of.primary_address(&deps.api).unwrap(), address);
+    let other_set = CosmosAuthData {
+        credentials: vec![credential(1, true), credential(2, true), credential(4, true)],
+        ..auth.clone()
+    };
+    assert_ne!(other_set.primary_address(&deps.api).unwrap(), address);
+
+    // invalid configurations
+    assert!(CosmosAuthData { threshold: Some(0), ..auth.clone() }.verify(&deps.api, &deps.storage).is_err());
+    assert!(CosmosAuthData { threshold: Some(4), ..auth.clone() }.verify(&deps.api, &deps.storage).is_err());
+    assert!(CosmosAuthData { primary_index: Some(0), ..auth.clone() }.verify(&deps.api, &deps.storage).is_err());
+    let duplicated = CosmosAuthData {
+        credentials: vec![credential(1, true), credential(1, true), credential(3, false)],
+        ..auth
+    };
+    assert!(duplicated.verify(&deps.api, &deps.storage).is_err());
+}
+
+
+
+
+#[test]
+fn legacy_auth_data_credentials() {
+    use crate::{CosmosAuthData, Credential};
+
+    let deps = mock_dependencies();
+    let legacy = format!(
+        r#"{{"credentials":[{{"signature":"{}","message":"{}","pubkey":"{}","hrp":"{}"}}],"primary_index":null,"threshold":null}}"#,
+        SIGNATURE, SIGNED_MSG, SIGNING_PUBKEY, SIGN_HRP
+    );
+    let auth : CosmosAuthData = cosmwasm_std::from_slice(legacy.as_bytes()).unwrap();
+
+    let credential = CosmosCredential {
+        signature: Binary::from_base64(SIGNATURE).unwrap(),
+        message: SIGNED_MSG.to_string(),
+        pubkey: Binary::from_base64(SIGNING_PUBKEY).unwrap(),
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
+    };
+    assert_eq!(auth.credentials, vec![Credential::CosmosArbitrary(credential)]);
+    auth.verify(&deps.api, &deps.storage).unwrap();
+
+    // tagged credentials keep working
+    let tagged = cosmwasm_std::to_vec(&auth).unwrap();
+    assert_eq!(cosmwasm_std::from_slice::<CosmosAuthData>(&tagged).unwrap(), auth);
+}
+
+
+#[test]
+fn test_amino_multisig() {
+    use crate::{CosmosAuthData, MultisigCredential};
+
+    let deps = mock_dependencies();
+    let secp = secp256k1::Secp256k1::signing_only();
+
+    let keys : Vec<secp256k1::SecretKey> = [21u8, 22, 23].iter()
+        .map(|seed| secret_key_from_bytes(&[*seed; 32].to_vec()).unwrap())
+        .collect();
+    let pubkeys : Vec<Binary> = keys.iter()
+        .map(|k| Binary(k.public_key(&secp).serialize().to_vec()))
+        .collect();
+
+    // prefix, threshold and the first member of the amino encoded key
+    let amino = multisig_pubkey_to_amino(2, &pubkeys).unwrap();
+    assert_eq!(
+        hex::encode(&amino[..46]), 
+        format!("22c1f7e208021226eb5ae98721{}", hex::encode(pubkeys[0].as_slice()))
+    );
+    assert_eq!(amino.len(), 4 + 2 + 3 * 40);
+
+    let address = multisig_to_address(2, &pubkeys, SIGN_HRP).unwrap();
+    assert_eq!(
+        address,
+        bech32::encode::<bech32::Bech32>(bech32::Hrp::parse(SIGN_HRP).unwrap(), &sha_256(&amino)[..20]).unwrap()
+    );
+
+    let sign = |i: usize| Some(client::sign_arbitrary(&keys[i], &address, SIGNED_MSG, &crate::AddressScheme::Cosmos));
+
+    let cred = MultisigCredential {
+        threshold: 2,
+        pubkeys: pubkeys.clone(),
+        signatures: vec![sign(0), None, sign(2)],
+        message: SIGNED_MSG.to_string(),
+        hrp: SIGN_HRP.to_string(),
+    };
+    assert_eq!(verify_multisig_arbitrary(&deps.api, &deps.storage, &cred).unwrap(), address);
+
+    // below the threshold
+    let one = MultisigCredential { signatures: vec![sign(0), None, None], ..cred.clone() };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &one).is_err());
+
+    // invalid signature of a member
+    let invalid = MultisigCredential { signatures: vec![sign(0), sign(0), sign(2)], ..cred.clone() };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &invalid).is_err());
+
+    // signed for the address of a single member
+    let member_address = pubkey_to_address(&pubkeys[0], SIGN_HRP).unwrap();
+    let wrong_signer = MultisigCredential { 
+        signatures: vec![
+            Some(client::sign_arbitrary(&keys[0], &member_address, SIGNED_MSG, &crate::AddressScheme::Cosmos)), 
+            None, 
+            sign(2)
+        ], 
+        ..cred.clone() 
+    };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &wrong_signer).is_err());
+
+    // malformed credentials
+    let missing = MultisigCredential { signatures: vec![sign(0), sign(2)], ..cred.clone() };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &missing).is_err());
+    let duplicated = MultisigCredential { 
+        pubkeys: vec![pubkeys[0].clone(), pubkeys[0].clone(), pubkeys[2].clone()], 
+        ..cred.clone() 
+    };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &duplicated).is_err());
+    let too_high = MultisigCredential { threshold: 4, ..cred.clone() };
+    assert!(verify_multisig_arbitrary(&deps.api, &deps.storage, &too_high).is_err());
+
+    let auth = CosmosAuthData {
+        credentials: vec![cred.into()],
+        primary_index: None,
+        threshold: None,
+    };
+    auth.verify(&deps.api, &deps.storage).unwrap();
+    assert_eq!(auth.primary_address(&deps.api).unwrap(), address);
+    assert_eq!(auth.primary_id(), amino);
+}
+
+
+
+#[test]
+fn encryption_to_retired_key() {
+    use crate::{common::{handle_encrypted_wrapper, rotate_encryption_wallet, set_key_grace_period}, gateway::GatewayExecuteMsg, types::Duration};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    set_key_grace_period(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+    rotate_encryption_wallet(&mut deps.storage, &env.block, wallets::SecretEncryptionWallet {
+        private_key: Binary::from_base64(CONTRACT_PRIVATE).unwrap(),
+        public_key: Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+    }).unwrap();
+
+    // a message encrypted to the key of the first epoch is in flight during the rotation
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+
+    let new_key = client::random_secret_key();
+    rotate_encryption_wallet(&mut deps.storage, &env.block, wallets::SecretEncryptionWallet {
+        private_key: Binary(new_key.secret_bytes().to_vec()),
+        public_key: Binary(new_key.public_key(&secp256k1::Secp256k1::signing_only()).serialize().to_vec()),
+    }).unwrap();
+
+    let mut wrapper = |builder: &client::EncryptedParamsBuilder, block: &cosmwasm_std::BlockInfo| {
+        let mut env = env.clone();
+        env.block = block.clone();
+        handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            &env,
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(builder.build(&payload).unwrap())
+        )
+    };
+
+    // not the current key
+    assert!(wrapper(&builder, &env.block).is_err());
+
+    let old_epoch = builder.clone().epoch(0);
+    assert_eq!(wrapper(&old_epoch, &env.block).unwrap().0, inner);
+
+    let mut later = env.block.clone();
+    later.height += 11;
+    assert!(wrapper(&old_epoch, &later).is_err());
+}
+
+
+
+#[test]
+fn test_hkdf_sha256() {
+    // RFC 5869 test case 1 truncated to the first block
+    let okm = utils::hkdf_sha256(
+        &[0x0b; 22],
+        &hex::decode("000102030405060708090a0b0c").unwrap(),
+        &hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap(),
+    );
+    assert_eq!(
+        hex::encode(okm), 
+        "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+    );
+
+    // test case 3 with an empty salt and info as used for deriving keys
+    let okm = utils::hkdf_sha256(&[0x0b; 22], &[], &[]);
+    assert_eq!(
+        hex::encode(okm), 
+        "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+    );
+}
+
+
+
+#[test]
+fn versioned_key_derivation() {
+    use crate::{common::handle_encrypted_wrapper_with_context, gateway::GatewayExecuteMsg};
+    use cosmwasm_std::{Response, testing::{mock_env, mock_info}};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let gateway_key = Binary::from_base64(CONTRACT_PUBLIC).unwrap();
+    let ephemeral = [5u8; 32].to_vec();
+    let builder = client::EncryptedParamsBuilder::new(&gateway_key, &USER_PRIVATE.to_vec())
+        .unwrap()
+        .ephemeral_key(&ephemeral).unwrap()
+        .version(PROTOCOL_VERSION);
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+
+    // the context of the gateway is required
+    assert!(builder.build(&payload).is_err());
+    assert!(builder.clone().epoch(0).build(&payload).is_err());
+
+    let builder = builder
+        .contract(env.contract.address.as_str(), &env.block.chain_id)
+        .epoch(0);
+    let params = builder.build(&payload).unwrap();
+
+    // the derived key differs from the raw shared secret
+    let legacy = get_common_key(
+        public_key_from_bytes(&gateway_key).unwrap(), 
+        secret_key_from_bytes(&ephemeral).unwrap()
+    );
+    assert_ne!(builder.response_key().unwrap(), legacy);
+
+    let mut wrapper = |env: &cosmwasm_std::Env, params: &crate::EncryptedParams| {
+        handle_encrypted_wrapper_with_context(
+            &deps.api, 
+            &mut deps.storage, 
+            env,
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        )
+    };
+
+    // can't be replayed against another gateway sharing the key
+    let mut other = env.clone();
+    other.contract.address = cosmwasm_std::Addr::unchecked("other_gateway");
+    assert!(wrapper(&other, &params).is_err());
+
+    let mut other = env.clone();
+    other.block.chain_id = "other-chain".to_string();
+    assert!(wrapper(&other, &params).is_err());
+
+    // unknown versions are rejected
+    assert!(wrapper(&env, &crate::EncryptedParams { version: Some(7), ..params.clone() }).is_err());
+
+    let (msg, _, context) = wrapper(&env, &params).unwrap();
+    assert_eq!(msg, inner);
+
+    let response : Response = Response::new().set_data(b"private data");
+    let encrypted = context.unwrap().encrypt_response(&env.block, response, &[]).unwrap();
+    let data : crate::EncryptedResponse = cosmwasm_std::from_binary(&encrypted.data.unwrap()).unwrap();
+
+    assert!(client::decrypt_response_with_key(&legacy, &data).is_err());
+    assert_eq!(
+        client::decrypt_response_with_key(&builder.response_key().unwrap(), &data).unwrap(), 
+        Binary(b"private data".to_vec())
+    );
+}
+
+
+
+#[test]
+fn cipher_options() {
+    use crate::{common::handle_encrypted_wrapper, gateway::GatewayExecuteMsg, Cipher, EncryptedParams};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let wallet = wallets::SecretEncryptionWallet {
+        private_key: Binary::from_base64(CONTRACT_PRIVATE).unwrap(),
+        public_key: Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+    };
+    let builder = client::EncryptedParamsBuilder::new(&wallet.public_key, &USER_PRIVATE.to_vec()).unwrap();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+
+    // random nonces are only safe with a 24 bytes one
+    let params = builder.build(&payload).unwrap();
+    assert_eq!(params.cipher, Some(Cipher::Xchacha20Poly1305));
+    assert_eq!(params.nonce.len(), 24);
+
+    for cipher in [Cipher::Chacha20Poly1305, Cipher::Xchacha20Poly1305, Cipher::Aes256GcmSiv] {
+        let params = builder.clone().cipher(cipher.clone()).build(&payload).unwrap();
+        assert_eq!(params.nonce.len(), cipher.nonce_size());
+
+        let decrypted = wallet.decrypt_bytes(&params.payload, &params.user_key, &params.nonce, &cipher).unwrap();
+        assert_eq!(cosmwasm_std::from_slice::<crate::EncryptedPayload>(&decrypted).unwrap(), payload);
+
+        // the cipher declared in the envelope must match
+        for other in [Cipher::Chacha20Poly1305, Cipher::Xchacha20Poly1305, Cipher::Aes256GcmSiv] {
+            if other != cipher {
+                assert!(wallet.decrypt_bytes(&params.payload, &params.user_key, &params.nonce, &other).is_err());
+            }
+        }
+
+        let (msg, _) = handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params)
+        ).unwrap();
+        assert_eq!(msg, inner);
+    }
+
+    // nonce of a wrong length is rejected without panicking
+    let params = builder.clone()
+        .nonce(&vec![1u8; 12]).unwrap()
+        .cipher(Cipher::Xchacha20Poly1305);
+    assert!(params.build(&payload).is_err());
+
+    let params = builder.clone().cipher(Cipher::Xchacha20Poly1305).build(&payload).unwrap();
+    assert!(handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(EncryptedParams { 
+            nonce: Binary(params.nonce[..12].to_vec()), 
+            ..params 
+        })
+    ).is_err());
+}
+
+
+
+#[test]
+fn associated_data_binding() {
+    use crate::{common::{handle_encrypted_wrapper, decrypt_query}, gateway::GatewayExecuteMsg, EncryptedParams};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+
+    // the contract is required for building the associated data
+    assert!(builder.clone().bind_contract().build(&payload).is_err());
+
+    let builder = builder.contract(env.contract.address.as_str(), &env.block.chain_id);
+
+    // queries have no relayer to bind
+    let relayed = builder.clone().bind_relayer("relayer").build(&payload).unwrap();
+    assert!(decrypt_query::<GatewayExecuteMsg>(&deps.api, &deps.storage, &env, &relayed).is_err());
+
+    let mut wrapper = |env: &cosmwasm_std::Env, sender: &str, params: &EncryptedParams| {
+        handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            env,
+            mock_info(sender, &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        )
+    };
+
+    let params = builder.clone().bind_contract().build(&payload).unwrap();
+
+    // relayed to another contract or chain
+    let mut other = env.clone();
+    other.contract.address = cosmwasm_std::Addr::unchecked("other_gateway");
+    assert!(wrapper(&other, "relayer", &params).is_err());
+    let mut other = env.clone();
+    other.block.chain_id = "other-chain".to_string();
+    assert!(wrapper(&other, "relayer", &params).is_err());
+
+    // associated data can't be stripped
+    assert!(wrapper(&env, "relayer", &EncryptedParams { associated_data: None, ..params.clone() }).is_err());
+
+    // any relayer can submit the message
+    assert_eq!(wrapper(&env, "anyone", &params).unwrap().0, inner);
+
+    assert!(wrapper(&env, "anyone", &relayed).is_err());
+    assert_eq!(wrapper(&env, "relayer", &relayed).unwrap().0, inner);
+}
+
+
+
+#[test]
+fn typed_encryption_errors() {
+    use crate::{common::handle_encrypted_wrapper, errors::EncryptionError, gateway::GatewayExecuteMsg, EncryptedParams};
+    use cosmwasm_std::{StdError, testing::{mock_env, mock_info}};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let params = builder.build(&encrypted_test_payload(&builder, &inner)).unwrap();
+
+    let mut wrapper = |params: &EncryptedParams| {
+        handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(msg, _)| msg)
+    };
+
+    assert_eq!(
+        wrapper(&EncryptedParams { nonce: Binary(vec![0u8; 8]), ..params.clone() }),
+        Err(EncryptionError::InvalidNonceLength { expected: 24, actual: 8 })
+    );
+    assert!(matches!(
+        wrapper(&EncryptedParams { user_key: Binary(vec![2u8; 12]), ..params.clone() }),
+        Err(EncryptionError::InvalidPubkey { .. })
+    ));
+
+    let mut tampered = params.payload.0.clone();
+    tampered[0] ^= 1;
+    assert_eq!(
+        wrapper(&EncryptedParams { payload: Binary(tampered.clone()), ..params.clone() }),
+        Err(EncryptionError::HashMismatch {})
+    );
+    assert_eq!(
+        wrapper(&EncryptedParams { 
+            payload_hash: Binary(sha_256(&tampered).to_vec()), 
+            payload: Binary(tampered), 
+            ..params.clone() 
+        }),
+        Err(EncryptionError::AeadFailure {})
+    );
+
+    let other = builder.build(&encrypted_test_payload(&builder, &inner)).unwrap();
+    assert!(matches!(
+        wrapper(&EncryptedParams { payload_signature: other.payload_signature.clone(), ..params.clone() }),
+        Err(EncryptionError::InvalidSignature { .. })
+    ));
+
+    assert_eq!(wrapper(&params), Ok(inner.clone()));
+    assert_eq!(wrapper(&params), Err(EncryptionError::Replay {}));
+
+    let nested = GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(other);
+    let nested_params = builder.build(&crate::EncryptedPayload {
+        msg: cosmwasm_std::to_binary(&nested).unwrap(),
+        ..encrypted_test_payload(&builder, &inner)
+    }).unwrap();
+    assert_eq!(wrapper(&nested_params), Err(EncryptionError::NestedEncryption {}));
+
+    // codes are preserved when converted to generic errors
+    let err : StdError = EncryptionError::Replay {}.into();
+    assert!(err.to_string().contains("ENC06"));
+    assert_eq!(EncryptionError::AeadFailure {}.code(), Some("ENC03"));
+}
+
+
+
+#[test]
+fn payload_hash_binding() {
+    use crate::{common::handle_encrypted_wrapper, errors::EncryptionError, gateway::GatewayExecuteMsg, EncryptedParams};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+
+    let legacy = builder.build(&payload).unwrap();
+    assert_eq!(legacy.payload_hash.as_slice(), sha_256(&legacy.payload).as_slice());
+
+    let builder = builder
+        .contract(env.contract.address.as_str(), &env.block.chain_id)
+        .epoch(0)
+        .version(PROTOCOL_VERSION);
+    let first = builder.build(&payload).unwrap();
+    let second = builder.build(&payload).unwrap();
+    assert_eq!(
+        first.payload_hash.as_slice(), 
+        payload_hash(first.version, &first.payload, &first.nonce, &first.user_key).as_slice()
+    );
+    assert_ne!(first.payload_hash.as_slice(), sha_256(&first.payload).as_slice());
+
+    let mut wrapper = |params: &EncryptedParams| {
+        handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            &env,
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(msg, _)| msg)
+    };
+
+    // a valid signature over one hash paired with a different payload
+    for params in [&legacy, &first] {
+        assert_eq!(
+            wrapper(&EncryptedParams { 
+                payload: second.payload.clone(), 
+                nonce: second.nonce.clone(),
+                user_key: second.user_key.clone(),
+                ..params.clone() 
+            }),
+            Err(EncryptionError::HashMismatch {})
+        );
+    }
+
+    // the nonce and the user key are covered by the hash of newer versions
+    assert_eq!(
+        wrapper(&EncryptedParams { nonce: second.nonce.clone(), ..first.clone() }),
+        Err(EncryptionError::HashMismatch {})
+    );
+    assert_eq!(
+        wrapper(&EncryptedParams { user_key: second.user_key.clone(), ..first.clone() }),
+        Err(EncryptionError::HashMismatch {})
+    );
+
+    // downgrading the version changes the expected hash
+    assert_eq!(
+        wrapper(&EncryptedParams { version: None, ..first.clone() }),
+        Err(EncryptionError::HashMismatch {})
+    );
+
+    assert_eq!(wrapper(&legacy), Ok(inner.clone()));
+    assert_eq!(wrapper(&first), Ok(inner));
+}
+
+
+
+#[test]
+fn declared_user_address() {
+    use crate::{common::handle_encrypted_wrapper, errors::EncryptionError, gateway::GatewayExecuteMsg, EncryptedParams};
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let payload = encrypted_test_payload(&builder, &inner);
+    let pubkey = builder.signing_pubkey();
+
+    let mut wrapper = |params: &EncryptedParams| {
+        handle_encrypted_wrapper(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info("relayer", &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(msg, info)| (msg, info.sender.to_string()))
+    };
+
+    // valid signature of a key declaring someone else's address
+    let declared = crate::EncryptedPayload { user_address: SIGNER.to_string(), ..payload.clone() };
+    assert!(builder.build(&declared).is_err());
+
+    let mut params = builder.encrypt(&declared).unwrap();
+    params.payload_signature = client::sign_arbitrary(
+        &secret_key_from_bytes(&USER_PRIVATE.to_vec()).unwrap(),
+        &payload.user_address,
+        &params.payload_hash.to_base64(),
+        &AddressScheme::Cosmos
+    );
+    assert_eq!(
+        wrapper(&params), 
+        Err(EncryptionError::AddressMismatch { 
+            declared: SIGNER.to_string(), 
+            derived: payload.user_address.clone() 
+        })
+    );
+
+    // declared scheme selects how the address is derived and verified
+    let eth_scheme = pubkey_to_address_with_scheme(&pubkey, SIGN_HRP, &AddressScheme::EthSecp256k1).unwrap();
+    let with_scheme = crate::EncryptedPayload { 
+        user_address: eth_scheme.clone(),
+        scheme: Some(AddressScheme::EthSecp256k1), 
+        ..payload.clone() 
+    };
+    assert!(builder.build(&crate::EncryptedPayload { scheme: None, ..with_scheme.clone() }).is_err());
+    assert!(builder.build(&crate::EncryptedPayload { user_address: payload.user_address.clone(), ..with_scheme.clone() }).is_err());
+
+    let params = builder.build(&with_scheme).unwrap();
+    assert!(wrapper(&EncryptedParams { 
+        payload_signature: builder.build(&payload).unwrap().payload_signature, 
+        ..params.clone() 
+    }).is_err());
+    assert_eq!(wrapper(&params), Ok((inner, eth_scheme)));
+}
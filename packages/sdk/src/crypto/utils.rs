@@ -1,7 +1,10 @@
 use std::ops::Deref;
 use ripemd::{Ripemd160, Digest};
+use sha3::Keccak256;
+use sha2::Sha256;
+use hkdf::Hkdf;
 use secp256k1::{ecdh::SharedSecret, PublicKey, SecretKey};
-use cosmwasm_std::{StdError, StdResult};
+use cosmwasm_std::{Binary, StdError, StdResult};
 
 
 /// Computes the ripemd160 hash of the given bytes.
@@ -12,6 +15,73 @@ pub fn ripemd160(bytes: &[u8]) -> Vec<u8> {
 }
 
 
+/// Compresses an uncompressed SEC1 secp256k1 or P-256 public key so that the same key
+/// is identified the same way in any encoding. Other bytes are returned as is
+pub fn normalize_pubkey(pubkey: &[u8]) -> Vec<u8> {
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+    if pubkey.len() != 65 {
+        return pubkey.to_vec();
+    }
+    if let Ok(key) = PublicKey::from_slice(pubkey) {
+        return key.serialize().to_vec();
+    }
+    match p256::PublicKey::from_sec1_bytes(pubkey) {
+        Ok(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        Err(_) => pubkey.to_vec()
+    }
+}
+
+
+/// Computes the keccak256 hash of the given bytes.
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+
+/// Derives a 32 bytes key from the input key material with HKDF-SHA256 (RFC 5869)
+pub fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut okm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .expect("32 bytes is a valid length for HKDF-SHA256");
+    okm
+}
+
+
+/// Prefixes a message with EIP-191 preamble used by `personal_sign`
+pub fn preamble_msg_eth_191(message: &[u8]) -> Vec<u8> {
+    [
+        format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes(),
+        message
+    ].concat()
+}
+
+
+/// Encodes the bytes with URL safe base64 alphabet without padding used by WebAuthn
+pub fn to_base64url(bytes: &[u8]) -> String {
+    Binary(bytes.to_vec())
+        .to_base64()
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+
+/// Encodes the number as protobuf varint
+pub fn encode_varint(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    bytes
+}
+
+
 /// Creates a preamble message for arbitrary 036 messages.
 pub fn preamble_msg_arb_036(signer: &str, data: &str) -> String {
     format!(
@@ -5,7 +5,11 @@ use secret_toolkit::crypto::{sha_256, secp256k1::PrivateKey};
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{Api, Binary, BlockInfo, StdError, StdResult, from_binary};
 
-use crate::{crypto::{pubkey_to_address, pubkey_to_canonical}, EncryptedPayload};
+use crate::{
+    errors::EncryptionError,
+    crypto::{pubkey_to_address, pubkey_to_canonical, derive_encryption_key, decrypt_with_cipher, KeyContext}, 
+    Cipher, EncryptedParams, EncryptedPayload
+};
 
 use super::{
     utils::{secret_key_from_bytes, public_key_from_bytes, get_common_key},
@@ -27,7 +31,10 @@ pub fn generate_secret_wallet(
     hrp                 :       Option<String>,
 ) -> StdResult<SecretWallet> {
     
-    let entropy : [u8; 32] = sha_256(&block.random.as_ref().unwrap().0);
+    let random = block.random.as_ref().ok_or_else(||
+        StdError::generic_err("Block randomness is required for generating a wallet")
+    )?;
+    let entropy : [u8; 32] = sha_256(&random.0);
     let mnemonic = Mnemonic::from_entropy(entropy, bip32::Language::English);
     let seed = mnemonic.to_seed(password.unwrap_or_default().as_str());
 
@@ -54,9 +61,6 @@ pub fn generate_secret_wallet(
         None => api.addr_humanize(&pubkey_to_canonical(&public_key))?.to_string()
     };  
     
-    println!("private_key: {:?}", Binary(private_key.serialize().to_vec()).to_base64());
-    println!("public_key: {:?}", Binary(public_key.to_vec()).to_base64());
-
 
     Ok(SecretWallet { 
         mnemonic: mnemonic.phrase().to_string(),
@@ -83,11 +87,11 @@ pub struct ExposedWallet {
 }
 
 
-impl Into<ExposedWallet> for SecretWallet {
-    fn into(self) -> ExposedWallet {
+impl From<SecretWallet> for ExposedWallet {
+    fn from(wallet: SecretWallet) -> Self {
         ExposedWallet {
-            address: self.address,
-            public_key: self.public_key,
+            address: wallet.address,
+            public_key: wallet.public_key,
         }
     }
 }
@@ -113,20 +117,20 @@ pub struct Secp256k1Wallet {
 }
 
 
-impl Into<SecretFeegrantWallet> for SecretWallet {
-    fn into(self) -> SecretFeegrantWallet {
+impl From<SecretWallet> for SecretFeegrantWallet {
+    fn from(wallet: SecretWallet) -> Self {
         SecretFeegrantWallet {
-            address: self.address,
-            mnemonic: self.mnemonic,
+            address: wallet.address,
+            mnemonic: wallet.mnemonic,
         }
     }
 }
 
-impl Into<SecretEncryptionWallet> for SecretWallet {
-    fn into(self) -> SecretEncryptionWallet {
+impl From<SecretWallet> for SecretEncryptionWallet {
+    fn from(wallet: SecretWallet) -> Self {
         SecretEncryptionWallet {
-            private_key: self.private_key,
-            public_key: self.public_key,
+            private_key: wallet.private_key,
+            public_key: wallet.public_key,
         }
     }
 }
@@ -152,25 +156,29 @@ impl SecretEncryptionWallet {
     pub fn decryption_key(
         &self, 
         other_public:  &impl Deref<Target = [u8]>
-    ) -> StdResult<Vec<u8>> {
+    ) -> Result<Vec<u8>, EncryptionError> {
         Ok(get_common_key(
-            public_key_from_bytes(other_public)?,
+            public_key_from_bytes(other_public).map_err(EncryptionError::invalid_pubkey)?,
             secret_key_from_bytes(&self.private_key)?
         ))
     }
 
+    /// Decrypts the bytes with the key shared with the other party using the given cipher
     pub fn decrypt_bytes(
         &self,
         ciphertext      :   &impl Deref<Target = [u8]>,
         other_public    :   &impl Deref<Target = [u8]>,
         nonce           :   &impl Deref<Target = [u8]>,
-    ) -> StdResult<Vec<u8>> {
+        cipher          :   &Cipher,
+    ) -> Result<Vec<u8>, EncryptionError> {
         let key  = self.decryption_key(other_public)?;
 
-        chacha20poly1305_decrypt(
+        decrypt_with_cipher(
+            cipher,
             ciphertext, 
             &key,
-            nonce
+            nonce,
+            &[]
         )
     }
 
@@ -180,6 +188,22 @@ impl SecretEncryptionWallet {
         other_public    :   &impl Deref<Target = [u8]>,
         nonce           :   &impl Deref<Target = [u8]>,
     ) -> StdResult<EncryptedPayload> {
+        let (payload, _) = self.decrypt_to_payload_and_key(
+            ciphertext, 
+            other_public, 
+            nonce
+        )?;
+        Ok(payload)
+    }
+
+    /// Same as [SecretEncryptionWallet::decrypt_to_payload] but also returns the shared key
+    /// that can be used for encrypting a response back to the user
+    pub fn decrypt_to_payload_and_key(
+        &self,
+        ciphertext      :   &impl Deref<Target = [u8]>,
+        other_public    :   &impl Deref<Target = [u8]>,
+        nonce           :   &impl Deref<Target = [u8]>,
+    ) -> StdResult<(EncryptedPayload, Vec<u8>)> {
         let key  = self.decryption_key(other_public)?;
 
         let decrypted = chacha20poly1305_decrypt(
@@ -188,6 +212,38 @@ impl SecretEncryptionWallet {
             nonce
         )?;
 
-        from_binary(&Binary(decrypted))
+        Ok((from_binary(&Binary(decrypted))?, key))
+    }
+
+    /// Shared key derived from the ECDH secret according to the protocol version
+    pub fn encryption_key(
+        &self,
+        other_public    :   &impl Deref<Target = [u8]>,
+        version         :   Option<u8>,
+        context         :   &KeyContext,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Ok(derive_encryption_key(&self.decryption_key(other_public)?, version, context)?)
+    }
+
+    /// Decrypts the payload of the params with a key derived according to their protocol version
+    /// and the cipher they specify authenticating the associated data. Returns the payload and
+    /// the key that can be used for encrypting a response back to the user
+    pub fn decrypt_params(
+        &self,
+        params          :   &EncryptedParams,
+        context         :   &KeyContext,
+        aad             :   &[u8],
+    ) -> Result<(EncryptedPayload, Vec<u8>), EncryptionError> {
+        let key = self.encryption_key(&params.user_key, params.version, context)?;
+
+        let decrypted = decrypt_with_cipher(
+            &params.cipher.clone().unwrap_or_default(),
+            &params.payload, 
+            &key,
+            &params.nonce,
+            aad
+        )?;
+
+        Ok((from_binary(&Binary(decrypted))?, key))
     }
 }
\ No newline at end of file
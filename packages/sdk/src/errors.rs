@@ -0,0 +1,79 @@
+use cosmwasm_std::{StdError, Uint128};
+use thiserror::Error;
+
+
+
+/// Failures of handling encrypted messages that clients can tell apart by [EncryptionError::code]
+#[derive(Error, Debug, PartialEq)]
+pub enum EncryptionError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Nonce must be {expected} bytes long but is {actual}")]
+    InvalidNonceLength { expected: usize, actual: usize },
+
+    #[error("Invalid public key: {msg}")]
+    InvalidPubkey { msg: String },
+
+    #[error("Payload can't be decrypted with the given key, nonce and associated data")]
+    AeadFailure {},
+
+    #[error("Payload hash doesn't match the payload")]
+    HashMismatch {},
+
+    #[error("Invalid signature: {msg}")]
+    InvalidSignature { msg: String },
+
+    #[error("Nonce already used")]
+    Replay {},
+
+    #[error("Nested encryption is not allowed")]
+    NestedEncryption {},
+
+    #[error("Payload is signed by {derived} instead of the declared {declared}")]
+    AddressMismatch { declared: String, derived: String },
+
+    #[error("Message must be relayed by {expected} instead of {actual}")]
+    UnexpectedRelayer { expected: String, actual: String },
+
+    #[error("Relayer fee of {required} exceeds the deposit of {available}")]
+    InsufficientFeeDeposit { required: Uint128, available: Uint128 },
+}
+
+
+impl EncryptionError {
+    /// Stable code of the error. Generic errors have no code
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            EncryptionError::Std(_)                         => None,
+            EncryptionError::InvalidNonceLength { .. }      => Some("ENC01"),
+            EncryptionError::InvalidPubkey { .. }           => Some("ENC02"),
+            EncryptionError::AeadFailure {}                 => Some("ENC03"),
+            EncryptionError::HashMismatch {}                => Some("ENC04"),
+            EncryptionError::InvalidSignature { .. }        => Some("ENC05"),
+            EncryptionError::Replay {}                      => Some("ENC06"),
+            EncryptionError::NestedEncryption {}            => Some("ENC07"),
+            EncryptionError::AddressMismatch { .. }         => Some("ENC08"),
+            EncryptionError::UnexpectedRelayer { .. }       => Some("ENC09"),
+            EncryptionError::InsufficientFeeDeposit { .. }  => Some("ENC10"),
+        }
+    }
+
+    pub fn invalid_pubkey(err: impl ToString) -> Self {
+        EncryptionError::InvalidPubkey { msg: err.to_string() }
+    }
+
+    pub fn invalid_signature(err: impl ToString) -> Self {
+        EncryptionError::InvalidSignature { msg: err.to_string() }
+    }
+}
+
+
+impl From<EncryptionError> for StdError {
+    fn from(err: EncryptionError) -> Self {
+        match err {
+            EncryptionError::Std(err) => err,
+            err => StdError::generic_err(format!("{}: {}", err.code().unwrap_or_default(), err)),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{ensure, to_binary, Api, Binary, BlockInfo, CosmosMsg, Env, Response, StdError, StdResult, Storage, Timestamp, Uint128};
+use secret_toolkit::{
+    crypto::sha_256,
+    storage::{Item, Keymap, KeymapBuilder, WithoutIter},
+    serialization::Bincode2
+};
+
+use crate::{
+    common::EncryptionContext,
+    crypto::{utils::encode_varint, wallets::{generate_secret_wallet, SecretFeegrantWallet}},
+    traits::SCRT_DENOM,
+    types::{Duration, Expiration}
+};
+
+
+
+pub const MSG_GRANT_ALLOWANCE_TYPE_URL  : &str = "/cosmos.feegrant.v1beta1.MsgGrantAllowance";
+pub const MSG_REVOKE_ALLOWANCE_TYPE_URL : &str = "/cosmos.feegrant.v1beta1.MsgRevokeAllowance";
+pub const BASIC_ALLOWANCE_TYPE_URL      : &str = "/cosmos.feegrant.v1beta1.BasicAllowance";
+
+
+
+/// Settings of the fee allowances the gateway grants to feegrant wallets
+#[cw_serde]
+pub struct FeegrantConfig {
+    /// Maximal amount of `uscrt` a wallet can spend on fees per grant. Unlimited if not provided
+    pub spend_limit :   Option<Uint128>,
+    /// Time for which a grant is valid. Grants don't expire if not provided
+    pub duration    :   Option<Duration>,
+    /// Whether all the users share one wallet instead of getting one each
+    pub shared      :   bool,
+    /// Maximal number of allowances granted to one account. Unlimited if not provided
+    pub max_grants  :   Option<u32>,
+}
+
+
+
+/// Feegrant wallet of a user and the moment its allowance expires
+#[cw_serde]
+pub struct FeegrantInfo {
+    /// Wallet that can pay fees with the allowance granted by the gateway
+    pub wallet      :   SecretFeegrantWallet,
+    /// Moment after which the allowance is no longer valid.
+    /// `None` if it doesn't expire
+    pub expires     :   Option<Expiration>,
+}
+
+
+
+/// Public part of a feegrant wallet that can be returned by queries
+#[cw_serde]
+pub struct FeegrantWalletResponse {
+    /// Address of the wallet that can pay fees with the allowance
+    pub address     :   String,
+    /// Moment after which the allowance is no longer valid.
+    /// `None` if it doesn't expire
+    pub expires     :   Option<Expiration>,
+}
+
+
+impl From<FeegrantInfo> for FeegrantWalletResponse {
+    fn from(info: FeegrantInfo) -> Self {
+        FeegrantWalletResponse {
+            address     :   info.wallet.address,
+            expires     :   info.expires,
+        }
+    }
+}
+
+
+
+// settings of the fee allowances. Feegrants are disabled if not set
+pub const FEEGRANT_CONFIG       :    Item<FeegrantConfig>   =    Item::new(b"feegrant_config");
+
+// wallet shared by all the users if the config says so
+pub const SHARED_FEEGRANT       :    Item<FeegrantInfo>     =    Item::new(b"feegrant_shared");
+
+// a mapping of accounts to their own feegrant wallets
+#[allow(clippy::declare_interior_mutable_const)]
+pub const FEEGRANT_WALLETS      :    Keymap<String, FeegrantInfo, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"feegrant_wallets").without_iter().build();
+
+// a mapping of accounts to the number of allowances granted to them
+#[allow(clippy::declare_interior_mutable_const)]
+pub const FEEGRANT_COUNTS       :    Keymap<String, u32, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"feegrant_counts").without_iter().build();
+
+
+
+/// Enables feegrants with the given settings. Passing `None` stops issuing new grants
+pub fn set_feegrant_config(
+    storage     :   &mut dyn Storage,
+    config      :   Option<FeegrantConfig>,
+) -> StdResult<()> {
+    match config {
+        Some(config) => {
+            ensure!(
+                !matches!(config.duration, Some(Duration::Height(_))),
+                StdError::generic_err("Fee allowances can only expire at a time")
+            );
+            FEEGRANT_CONFIG.save(storage, &config)
+        },
+        None => {
+            FEEGRANT_CONFIG.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Returns the feegrant wallet the account can use. The shared one if the gateway is configured so
+pub fn feegrant_info(
+    storage     :   &dyn Storage,
+    account     :   &str,
+) -> StdResult<Option<FeegrantInfo>> {
+    let shared = FEEGRANT_CONFIG.may_load(storage)?.map(|c| c.shared).unwrap_or(false);
+    if shared {
+        SHARED_FEEGRANT.may_load(storage)
+    } else {
+        Ok(FEEGRANT_WALLETS.get(storage, &account.to_string()))
+    }
+}
+
+
+
+/// Encodes a length delimited protobuf field
+fn proto_field(
+    field       :   u8,
+    bytes       :   &[u8],
+) -> Vec<u8> {
+    [vec![field << 3 | 2], encode_varint(bytes.len() as u64), bytes.to_vec()].concat()
+}
+
+
+/// Encodes `google.protobuf.Timestamp`
+fn proto_timestamp(time: &Timestamp) -> Vec<u8> {
+    let mut bytes = vec![];
+    if time.seconds() > 0 {
+        bytes.push(1 << 3);
+        bytes.extend(encode_varint(time.seconds()));
+    }
+    if time.subsec_nanos() > 0 {
+        bytes.push(2 << 3);
+        bytes.extend(encode_varint(time.subsec_nanos()));
+    }
+    bytes
+}
+
+
+
+/// Protobuf encoded `BasicAllowance` with an optional spend limit in `uscrt` and expiration.
+/// Allowances can't expire at a block height
+pub fn basic_allowance(
+    spend_limit :   Option<Uint128>,
+    expiration  :   Option<&Expiration>,
+) -> StdResult<Vec<u8>> {
+    let mut bytes = vec![];
+
+    if let Some(limit) = spend_limit {
+        let coin = [
+            proto_field(1, SCRT_DENOM.as_bytes()),
+            proto_field(2, limit.to_string().as_bytes())
+        ].concat();
+        bytes.extend(proto_field(1, &coin));
+    }
+
+    match expiration {
+        Some(Expiration::AtTime(time)) => bytes.extend(proto_field(2, &proto_timestamp(time))),
+        Some(Expiration::AtHeight(_)) => return Err(StdError::generic_err(
+            "Fee allowances can only expire at a time"
+        )),
+        Some(Expiration::Never {}) | None => {}
+    }
+
+    Ok(bytes)
+}
+
+
+
+/// Stargate message granting a `BasicAllowance` of the granter to the grantee
+pub fn grant_allowance_msg(
+    granter     :   &str,
+    grantee     :   &str,
+    spend_limit :   Option<Uint128>,
+    expiration  :   Option<&Expiration>,
+) -> StdResult<CosmosMsg> {
+    let allowance = [
+        proto_field(1, BASIC_ALLOWANCE_TYPE_URL.as_bytes()),
+        proto_field(2, &basic_allowance(spend_limit, expiration)?)
+    ].concat();
+
+    Ok(CosmosMsg::Stargate {
+        type_url: MSG_GRANT_ALLOWANCE_TYPE_URL.to_string(),
+        value: Binary([
+            proto_field(1, granter.as_bytes()),
+            proto_field(2, grantee.as_bytes()),
+            proto_field(3, &allowance)
+        ].concat())
+    })
+}
+
+
+
+/// Stargate message revoking an existing allowance of the granter to the grantee
+pub fn revoke_allowance_msg(
+    granter     :   &str,
+    grantee     :   &str,
+) -> CosmosMsg {
+    CosmosMsg::Stargate {
+        type_url: MSG_REVOKE_ALLOWANCE_TYPE_URL.to_string(),
+        value: Binary([
+            proto_field(1, granter.as_bytes()),
+            proto_field(2, grantee.as_bytes())
+        ].concat())
+    }
+}
+
+
+
+/// Generates a new feegrant wallet. The randomness of the block is mixed with the account
+/// so that wallets generated in the same block are different
+fn generate_feegrant_wallet(
+    api         :   &dyn Api,
+    block       :   &BlockInfo,
+    account     :   &str,
+    hrp         :   Option<String>,
+) -> StdResult<SecretFeegrantWallet> {
+    let mut block = block.clone();
+    block.random = block.random.map(|random|
+        Binary(sha_256(&[random.as_slice(), account.as_bytes()].concat()).to_vec())
+    );
+    Ok(generate_secret_wallet(api, &block, None, None, hrp)?.into())
+}
+
+
+
+/// Grants a fee allowance of the gateway to the feegrant wallet of the account generating
+/// the wallet first if needed. An allowance can only be renewed after it has expired
+/// and at most `max_grants` times per account. While the allowance of the shared wallet
+/// is active the wallet is returned to any account without granting a new one.
+/// The wallet with its mnemonic is put into the response data only for encrypted requests
+/// and the caller must encrypt it with [EncryptionContext::encrypt_response]
+pub fn handle_request_feegrant(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    env         :   &Env,
+    account     :   &str,
+    hrp         :   Option<String>,
+    context     :   Option<&EncryptionContext>,
+) -> StdResult<Response> {
+    let config = FEEGRANT_CONFIG.may_load(storage)?.ok_or_else(||
+        StdError::generic_err("Feegrants are not enabled")
+    )?;
+
+    let existing = feegrant_info(storage, account)?;
+    let active = existing
+        .as_ref()
+        .is_some_and(|info| !info.expires.as_ref().is_some_and(|e| e.is_expired(&env.block)));
+
+    let response = if active {
+        ensure!(config.shared, StdError::generic_err("Fee allowance of the wallet is still active"));
+        Response::new()
+    } else {
+        let granted = FEEGRANT_COUNTS.get(storage, &account.to_string()).unwrap_or_default();
+        ensure!(
+            !matches!(config.max_grants, Some(max) if granted >= max),
+            StdError::generic_err("Account has used up its fee allowances")
+        );
+
+        let wallet = match existing {
+            Some(info) => info.wallet,
+            None => {
+                let seed = if config.shared { "shared" } else { account };
+                generate_feegrant_wallet(api, &env.block, seed, hrp)?
+            }
+        };
+        let info = FeegrantInfo {
+            wallet,
+            expires     :   config.duration.map(|d| d.after(&env.block)),
+        };
+        FEEGRANT_COUNTS.insert(storage, &account.to_string(), &(granted + 1))?;
+        if config.shared {
+            SHARED_FEEGRANT.save(storage, &info)?;
+        } else {
+            FEEGRANT_WALLETS.insert(storage, &account.to_string(), &info)?;
+        }
+
+        Response::new().add_message(grant_allowance_msg(
+            env.contract.address.as_str(),
+            &info.wallet.address,
+            config.spend_limit,
+            info.expires.as_ref()
+        )?)
+    };
+
+    let wallet = feegrant_info(storage, account)?
+        .ok_or_else(|| StdError::generic_err("Feegrant wallet not found"))?
+        .wallet;
+    let response = response
+      .add_attribute("action", "request_feegrant")
+      .add_attribute("grantee", wallet.address.clone());
+
+    Ok(match context {
+        Some(_) => response.set_data(to_binary(&wallet)?),
+        None => response
+    })
+}
+
+
+
+/// Revokes the active allowance of the own feegrant wallet of the account and forgets
+/// the wallet so that a new one is generated on the next request e.g. after its mnemonic
+/// has leaked. The shared wallet can't be revoked this way
+pub fn handle_revoke_feegrant(
+    storage     :   &mut dyn Storage,
+    env         :   &Env,
+    account     :   &str,
+) -> StdResult<Response> {
+    let info = FEEGRANT_WALLETS.get(storage, &account.to_string()).ok_or_else(||
+        StdError::generic_err("Account has no feegrant wallet of its own")
+    )?;
+    FEEGRANT_WALLETS.remove(storage, &account.to_string())?;
+
+    let mut response = Response::new()
+      .add_attribute("action", "revoke_feegrant")
+      .add_attribute("grantee", info.wallet.address.clone());
+
+    // expired allowances are removed by the chain
+    if !info.expires.as_ref().is_some_and(|e| e.is_expired(&env.block)) {
+        response = response.add_message(revoke_allowance_msg(
+            env.contract.address.as_str(),
+            &info.wallet.address
+        ));
+    }
+    Ok(response)
+}
+
+
+
+#[cfg(test)]
+mod tests;
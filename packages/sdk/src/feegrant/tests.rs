@@ -0,0 +1,129 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use super::*;
+
+
+
+#[test]
+fn feegrant_wallets() {
+    // protobuf encoding of the messages
+    let coin = [&[0x0a, 5][..], b"uscrt", &[0x12, 1], b"5"].concat();
+    let allowance = [&[0x0a, coin.len() as u8][..], &coin, &[0x12, 2, 0x08, 1]].concat();
+    let any = [
+        &[0x0a, BASIC_ALLOWANCE_TYPE_URL.len() as u8][..], BASIC_ALLOWANCE_TYPE_URL.as_bytes(), 
+        &[0x12, allowance.len() as u8], &allowance
+    ].concat();
+    assert_eq!(
+        grant_allowance_msg("a", "b", Some(Uint128::new(5)), Some(&Expiration::AtTime(Timestamp::from_seconds(1)))),
+        Ok(CosmosMsg::Stargate { 
+            type_url: MSG_GRANT_ALLOWANCE_TYPE_URL.to_string(),
+            value: Binary([&[0x0a, 1][..], b"a", &[0x12, 1], b"b", &[0x1a, any.len() as u8], &any].concat())
+        })
+    );
+    assert_eq!(
+        revoke_allowance_msg("a", "b"),
+        CosmosMsg::Stargate { 
+            type_url: MSG_REVOKE_ALLOWANCE_TYPE_URL.to_string(),
+            value: Binary(vec![0x0a, 1, b'a', 0x12, 1, b'b'])
+        }
+    );
+    assert!(grant_allowance_msg("a", "b", None, Some(&Expiration::AtHeight(10))).is_err());
+
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let hrp = Some("secret".to_string());
+    let request = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, env: &cosmwasm_std::Env, account: &str| 
+        handle_request_feegrant(&deps.api, &mut deps.storage, env, account, hrp.clone(), None);
+
+    assert!(request(&mut deps, &env, "alice").is_err());
+    assert!(set_feegrant_config(&mut deps.storage, Some(FeegrantConfig { 
+        spend_limit: None, duration: Some(Duration::Height(10)), shared: false, max_grants: None 
+    })).is_err());
+
+    // every user gets a wallet of their own
+    set_feegrant_config(&mut deps.storage, Some(FeegrantConfig { 
+        spend_limit: Some(Uint128::new(1000)), duration: Some(Duration::Time(3600)), shared: false, max_grants: Some(2) 
+    })).unwrap();
+    let res = request(&mut deps, &env, "alice").unwrap();
+    let alice = feegrant_info(&deps.storage, "alice").unwrap().unwrap();
+    assert_eq!(res.attributes[1].value, alice.wallet.address);
+    assert_eq!(alice.expires, Some(Expiration::AtTime(env.block.time.plus_seconds(3600))));
+    assert!(matches!(
+        &res.messages[0].msg, 
+        CosmosMsg::Stargate { type_url, .. } if type_url == MSG_GRANT_ALLOWANCE_TYPE_URL
+    ));
+
+    // the mnemonic is only returned for encrypted requests
+    assert_eq!(res.data, None);
+    assert_eq!(
+        FeegrantWalletResponse::from(alice.clone()), 
+        FeegrantWalletResponse { address: alice.wallet.address.clone(), expires: alice.expires }
+    );
+    let context = EncryptionContext { 
+        key: vec![1u8; 32], nonce: Binary(vec![2u8; 12]), signer_id: vec![], relayer_fee: None 
+    };
+    let res = handle_request_feegrant(&deps.api, &mut deps.storage, &env, "bob", hrp.clone(), Some(&context)).unwrap();
+    let bob = feegrant_info(&deps.storage, "bob").unwrap().unwrap();
+    assert_ne!(alice.wallet.address, bob.wallet.address);
+    assert_eq!(res.data, Some(cosmwasm_std::to_binary(&bob.wallet).unwrap()));
+
+    // renewed for the same wallet only after the allowance has expired
+    assert!(request(&mut deps, &env, "alice").is_err());
+    let mut later = env.clone();
+    later.block.time = later.block.time.plus_seconds(3601);
+    request(&mut deps, &later, "alice").unwrap();
+    let renewed = feegrant_info(&deps.storage, "alice").unwrap().unwrap();
+    assert_eq!(renewed.wallet, alice.wallet);
+    assert_eq!(renewed.expires, Some(Expiration::AtTime(later.block.time.plus_seconds(3600))));
+
+    // up to the maximal number of grants
+    let mut expired = later.clone();
+    expired.block.time = expired.block.time.plus_seconds(3601);
+    assert!(request(&mut deps, &expired, "alice").is_err());
+
+    // an active allowance is revoked along with the wallet
+    let res = handle_revoke_feegrant(&mut deps.storage, &env, "bob").unwrap();
+    assert_eq!(res.messages[0].msg, revoke_allowance_msg(env.contract.address.as_str(), &bob.wallet.address));
+    assert_eq!(feegrant_info(&deps.storage, "bob").unwrap(), None);
+    assert!(handle_revoke_feegrant(&mut deps.storage, &env, "bob").is_err());
+    request(&mut deps, &env, "bob").unwrap();
+
+    // an expired one is already removed by the chain
+    let res = handle_revoke_feegrant(&mut deps.storage, &expired, "alice").unwrap();
+    assert!(res.messages.is_empty());
+
+}
+
+
+#[test]
+fn shared_feegrant_wallet() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let hrp = Some("secret".to_string());
+    let context = EncryptionContext { 
+        key: vec![1u8; 32], nonce: Binary(vec![2u8; 12]), signer_id: vec![], relayer_fee: None 
+    };
+    let request = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, env: &cosmwasm_std::Env, account: &str| 
+        handle_request_feegrant(&deps.api, &mut deps.storage, env, account, hrp.clone(), Some(&context));
+
+    set_feegrant_config(&mut deps.storage, Some(FeegrantConfig { 
+        spend_limit: None, duration: Some(Duration::Time(3600)), shared: true, max_grants: Some(1) 
+    })).unwrap();
+    let first = request(&mut deps, &env, "alice").unwrap();
+    assert_eq!(first.messages.len(), 1);
+
+    // other accounts get the same wallet without a new grant
+    let second = request(&mut deps, &env, "bob").unwrap();
+    assert!(second.messages.is_empty());
+    assert_eq!(second.data, first.data);
+    let shared = feegrant_info(&deps.storage, "bob").unwrap().unwrap();
+    assert_eq!(second.data, Some(cosmwasm_std::to_binary(&shared.wallet).unwrap()));
+    assert_eq!(feegrant_info(&deps.storage, "alice").unwrap(), Some(shared.clone()));
+
+    // only issued grants are counted
+    let mut later = env.clone();
+    later.block.time = later.block.time.plus_seconds(3601);
+    assert!(request(&mut deps, &later, "alice").is_err());
+    let renewed = request(&mut deps, &later, "bob").unwrap();
+    assert_eq!(renewed.messages.len(), 1);
+    assert_eq!(renewed.data, first.data);
+}
@@ -0,0 +1,294 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{coins, ensure, from_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Env, Response, StdError, StdResult, Storage, Uint128};
+use secret_toolkit::{
+    snip20::{register_receive_msg, transfer_msg},
+    storage::{Keymap, KeymapBuilder, WithoutIter},
+    serialization::Bincode2
+};
+
+use crate::{common::{EncryptionContext, BLOCK_SIZE}, errors::EncryptionError, traits::SCRT_DENOM};
+
+
+
+/// SNIP-20 token contract
+#[cw_serde]
+pub struct Snip20Token {
+    /// Address of the token contract
+    pub address     :   String,
+    /// Code hash of the token contract
+    pub code_hash   :   String,
+}
+
+
+
+/// Fee the user agrees to pay to the relayer submitting an encrypted message
+#[cw_serde]
+pub struct RelayerFee {
+    /// Maximal amount the user agrees to pay. Charged in full from the deposit of the user
+    pub amount      :   Uint128,
+    /// SNIP-20 token the fee is paid in. Native `uscrt` if not provided
+    pub token       :   Option<Snip20Token>,
+    /// Address of the only relayer allowed to submit the message.
+    /// Anyone can relay the message if not provided
+    pub relayer     :   Option<String>,
+}
+
+
+
+/// Optional message of a SNIP-20 `Send` depositing the tokens for relayer fees
+#[cw_serde]
+pub struct FeeDeposit {
+    /// Account credited with the deposit. The sender of the tokens if not provided
+    pub recipient   :   Option<String>,
+}
+
+
+
+// a mapping of accounts and tokens (`uscrt` or a SNIP-20 address) to the deposited amounts
+#[allow(clippy::declare_interior_mutable_const)]
+pub const FEE_DEPOSITS          :    Keymap<(String, String), Uint128, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"fee_deposits").without_iter().build();
+
+// a mapping of SNIP-20 contracts accepted for fee deposits to their code hashes
+#[allow(clippy::declare_interior_mutable_const)]
+pub const FEE_TOKENS            :    Keymap<String, String, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"fee_tokens").without_iter().build();
+
+
+
+fn token_key(token: Option<&Snip20Token>) -> String {
+    match token {
+        Some(token) => token.address.clone(),
+        None => SCRT_DENOM.to_string()
+    }
+}
+
+
+
+/// Accepts the SNIP-20 tokens for fee deposits. Returns the messages registering
+/// the gateway as the receiver of the tokens that must be added to the response
+pub fn register_fee_tokens(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    env         :   &Env,
+    tokens      :   Vec<Snip20Token>,
+) -> StdResult<Vec<CosmosMsg>> {
+    tokens
+        .into_iter()
+        .map(|token| {
+            let address = api.addr_validate(&token.address)?.to_string();
+            FEE_TOKENS.insert(storage, &address, &token.code_hash)?;
+            register_receive_msg(
+                env.contract.code_hash.clone(),
+                None,
+                BLOCK_SIZE,
+                token.code_hash,
+                address
+            )
+        })
+        .collect()
+}
+
+
+
+/// Whether the SNIP-20 token is accepted for fee deposits
+pub fn is_fee_token(
+    storage     :   &dyn Storage,
+    token       :   &Addr,
+) -> bool {
+    FEE_TOKENS.contains(storage, &token.to_string())
+}
+
+
+
+/// Amount of the token (native `uscrt` if `None`) deposited by the account for paying relayer fees
+pub fn fee_balance(
+    storage     :   &dyn Storage,
+    account     :   &str,
+    token       :   Option<&Snip20Token>,
+) -> Uint128 {
+    FEE_DEPOSITS
+        .get(storage, &(account.to_string(), token_key(token)))
+        .unwrap_or_default()
+}
+
+
+
+/// Credits the account with the given amount of the token stored under the key
+fn credit_fees(
+    storage     :   &mut dyn Storage,
+    account     :   &str,
+    key         :   String,
+    amount      :   Uint128,
+) -> StdResult<Uint128> {
+    let key = (account.to_string(), key);
+    let balance = FEE_DEPOSITS.get(storage, &key).unwrap_or_default().checked_add(amount)?;
+    FEE_DEPOSITS.insert(storage, &key, &balance)?;
+    Ok(balance)
+}
+
+
+
+/// Removes the amount from the deposit of the account. Fails if the deposit is lower
+fn debit_fees(
+    storage     :   &mut dyn Storage,
+    account     :   &str,
+    token       :   Option<&Snip20Token>,
+    amount      :   Uint128,
+) -> Result<(), EncryptionError> {
+    let key = (account.to_string(), token_key(token));
+    let available = FEE_DEPOSITS.get(storage, &key).unwrap_or_default();
+    ensure!(
+        available >= amount,
+        EncryptionError::InsufficientFeeDeposit { required: amount, available }
+    );
+
+    let left = available - amount;
+    if left.is_zero() {
+        FEE_DEPOSITS.remove(storage, &key)?;
+    } else {
+        FEE_DEPOSITS.insert(storage, &key, &left)?;
+    }
+    Ok(())
+}
+
+
+
+/// Message sending the amount of the token (native `uscrt` if `None`) to the recipient
+pub fn fee_transfer_msg(
+    recipient   :   &str,
+    token       :   Option<&Snip20Token>,
+    amount      :   Uint128,
+) -> StdResult<CosmosMsg> {
+    match token {
+        Some(token) => transfer_msg(
+            recipient.to_string(),
+            amount,
+            None,
+            None,
+            BLOCK_SIZE,
+            token.code_hash.clone(),
+            token.address.clone()
+        ),
+        None => Ok(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: coins(amount.u128(), SCRT_DENOM)
+        }.into())
+    }
+}
+
+
+
+/// Checks that the relayer is allowed to submit the message and charges the fee from
+/// the deposit of the payer. Returns the message paying the fee out to the relayer
+pub fn charge_relayer_fee(
+    storage     :   &mut dyn Storage,
+    relayer     :   &Addr,
+    payer       :   &str,
+    fee         :   &RelayerFee,
+) -> Result<Option<CosmosMsg>, EncryptionError> {
+    if let Some(pinned) = &fee.relayer {
+        ensure!(
+            pinned == relayer.as_str(),
+            EncryptionError::UnexpectedRelayer { expected: pinned.clone(), actual: relayer.to_string() }
+        );
+    }
+    if fee.amount.is_zero() {
+        return Ok(None);
+    }
+    debit_fees(storage, payer, fee.token.as_ref(), fee.amount)?;
+    Ok(Some(fee_transfer_msg(relayer.as_str(), fee.token.as_ref(), fee.amount)?))
+}
+
+
+
+/// Credits the attached `uscrt` to the deposit of the recipient
+pub fn handle_deposit_fees(
+    storage     :   &mut dyn Storage,
+    recipient   :   &str,
+    funds       :   &[Coin],
+) -> StdResult<Response> {
+    let amount = funds
+        .iter()
+        .filter(|coin| coin.denom == SCRT_DENOM)
+        .map(|coin| coin.amount)
+        .sum::<Uint128>();
+    ensure!(
+        !amount.is_zero(),
+        StdError::generic_err(format!("Fees must be deposited in {}", SCRT_DENOM))
+    );
+    let balance = credit_fees(storage, recipient, SCRT_DENOM.to_string(), amount)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "deposit_fees")
+      .add_attribute("amount", amount.to_string())
+      .add_attribute("balance", balance.to_string())
+    )
+}
+
+
+
+/// Credits SNIP-20 tokens sent to the gateway to the deposit of the sender or of the
+/// recipient named in the optional [FeeDeposit] message. The token is the sender
+/// of the `Receive` message and must be registered with [register_fee_tokens].
+/// Rejected inside encrypted messages where the sender is only a remote address
+pub fn handle_receive_fees(
+    storage     :   &mut dyn Storage,
+    token       :   &Addr,
+    from        :   &str,
+    amount      :   Uint128,
+    msg         :   Option<Binary>,
+    context     :   Option<&EncryptionContext>,
+) -> StdResult<Response> {
+    ensure!(
+        context.is_none(),
+        StdError::generic_err("Tokens can't be received through encrypted messages")
+    );
+    ensure!(
+        is_fee_token(storage, token),
+        StdError::generic_err(format!("Token {} is not accepted for fees", token))
+    );
+
+    let recipient = match msg {
+        Some(msg) => from_binary::<FeeDeposit>(&msg)?.recipient,
+        None => None
+    }.unwrap_or(from.to_string());
+
+    let balance = credit_fees(storage, &recipient, token.to_string(), amount)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "deposit_fees")
+      .add_attribute("amount", amount.to_string())
+      .add_attribute("balance", balance.to_string())
+    )
+}
+
+
+
+/// Sends the amount (whole deposit if not provided) of the token from the deposit of the account
+/// to the recipient. Accounts of remote chains must name a recipient on Secret Network
+pub fn handle_withdraw_fees(
+    storage     :   &mut dyn Storage,
+    account     :   &str,
+    token       :   Option<Snip20Token>,
+    amount      :   Option<Uint128>,
+    recipient   :   Option<String>,
+) -> StdResult<Response> {
+    let amount = amount.unwrap_or_else(|| fee_balance(storage, account, token.as_ref()));
+    ensure!(!amount.is_zero(), StdError::generic_err("Nothing to withdraw"));
+
+    debit_fees(storage, account, token.as_ref(), amount)?;
+
+    Ok(Response::new()
+      .add_message(fee_transfer_msg(&recipient.unwrap_or(account.to_string()), token.as_ref(), amount)?)
+      .add_attribute("action", "withdraw_fees")
+      .add_attribute("amount", amount.to_string())
+    )
+}
+
+
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,167 @@
+use cosmwasm_std::{coins, testing::{mock_dependencies, mock_env, mock_info}, Addr, BankMsg, CosmosMsg, WasmMsg};
+use crate::{
+    common::{handle_encrypted_wrapper, handle_encrypted_wrapper_with_context},
+    test_utils::{encrypted_test_builder, encrypted_test_payload, encrypted_test_setup},
+    gateway::GatewayExecuteMsg, EncryptedParams
+};
+use super::*;
+
+
+
+#[test]
+fn relayer_fees() {
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let user = encrypted_test_payload(&builder, &inner).user_address;
+    let token = Snip20Token { address: "token".to_string(), code_hash: "hash".to_string() };
+
+    let with_fee = |fee: RelayerFee| builder.build(&crate::EncryptedPayload {
+        fee: Some(fee),
+        ..encrypted_test_payload(&builder, &inner)
+    }).unwrap();
+    let native_fee = RelayerFee { amount: Uint128::new(100), token: None, relayer: None };
+
+    let mut wrapper = |params: &EncryptedParams, relayer: &str| {
+        handle_encrypted_wrapper_with_context(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info(relayer, &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(_, info, context)| (info.sender.to_string(), context.unwrap().relayer_fee))
+    };
+
+    assert_eq!(
+        wrapper(&with_fee(native_fee.clone()), "relayer"),
+        Err(EncryptionError::InsufficientFeeDeposit { required: Uint128::new(100), available: Uint128::zero() })
+    );
+
+    // a pinned relayer is checked even without a fee
+    let pinned = RelayerFee { amount: Uint128::zero(), relayer: Some("relayer".to_string()), ..native_fee.clone() };
+    assert_eq!(
+        wrapper(&with_fee(pinned.clone()), "other"),
+        Err(EncryptionError::UnexpectedRelayer { expected: "relayer".to_string(), actual: "other".to_string() })
+    );
+    assert_eq!(wrapper(&with_fee(pinned), "relayer"), Ok((user.clone(), None)));
+
+    assert!(handle_deposit_fees(&mut deps.storage, &user, &coins(150, "uatom")).is_err());
+    handle_deposit_fees(&mut deps.storage, &user, &coins(150, SCRT_DENOM)).unwrap();
+    register_fee_tokens(&deps.api, &mut deps.storage, &mock_env(), vec![token.clone()]).unwrap();
+    handle_receive_fees(
+        &mut deps.storage, 
+        &Addr::unchecked("token"), 
+        "depositor", 
+        Uint128::new(10), 
+        Some(cosmwasm_std::to_binary(&FeeDeposit { recipient: Some(user.clone()) }).unwrap()),
+        None
+    ).unwrap();
+    assert_eq!(fee_balance(&deps.storage, &user, Some(&token)), Uint128::new(10));
+    assert_eq!(fee_balance(&deps.storage, "depositor", Some(&token)), Uint128::zero());
+
+    let mut wrapper = |params: &EncryptedParams, relayer: &str| {
+        handle_encrypted_wrapper_with_context(
+            &deps.api, 
+            &mut deps.storage, 
+            &mock_env(),
+            mock_info(relayer, &[]), 
+            GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(params.clone())
+        ).map(|(_, info, context)| (info.sender.to_string(), context.unwrap().relayer_fee))
+    };
+
+    // the fee is paid to the sender of the message
+    assert_eq!(
+        wrapper(&with_fee(native_fee.clone()), "relayer"),
+        Ok((user.clone(), Some(CosmosMsg::Bank(BankMsg::Send { 
+            to_address: "relayer".to_string(), 
+            amount: coins(100, SCRT_DENOM) 
+        }))))
+    );
+    let snip_fee = RelayerFee { amount: Uint128::new(10), token: Some(token.clone()), relayer: None };
+    assert!(matches!(
+        wrapper(&with_fee(snip_fee), "relayer"),
+        Ok((_, Some(CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })))) if contract_addr == "token"
+    ));
+    assert_eq!(fee_balance(&deps.storage, &user, None), Uint128::new(50));
+    assert_eq!(fee_balance(&deps.storage, &user, Some(&token)), Uint128::zero());
+
+    // the wrapper without context can't pay the fee
+    assert!(handle_encrypted_wrapper(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(with_fee(native_fee))
+    ).is_err());
+
+    let res = handle_withdraw_fees(&mut deps.storage, &user, None, None, Some("secret1recipient".to_string())).unwrap();
+    assert_eq!(
+        res.messages[0].msg, 
+        CosmosMsg::Bank(BankMsg::Send { to_address: "secret1recipient".to_string(), amount: coins(50, SCRT_DENOM) })
+    );
+    assert!(handle_withdraw_fees(&mut deps.storage, &user, None, None, None).is_err());
+}
+
+
+
+#[test]
+fn registered_fee_tokens() {
+    let mut deps = mock_dependencies();
+    let token = Snip20Token { address: "token".to_string(), code_hash: "hash".to_string() };
+
+    let msgs = register_fee_tokens(&deps.api, &mut deps.storage, &mock_env(), vec![token]).unwrap();
+    assert!(matches!(
+        &msgs[..],
+        [CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, code_hash, .. })] 
+            if contract_addr == "token" && code_hash == "hash"
+    ));
+    assert!(is_fee_token(&deps.storage, &Addr::unchecked("token")));
+    assert!(!is_fee_token(&deps.storage, &Addr::unchecked("other")));
+}
+
+
+#[test]
+fn receive_from_unknown_token() {
+    let mut deps = mock_dependencies();
+    let token = Snip20Token { address: "token".to_string(), code_hash: "hash".to_string() };
+    register_fee_tokens(&deps.api, &mut deps.storage, &mock_env(), vec![token]).unwrap();
+
+    // anyone can call `Receive` claiming to be a token
+    assert!(handle_receive_fees(
+        &mut deps.storage, &Addr::unchecked("other"), "depositor", Uint128::new(10), None, None
+    ).is_err());
+    let other = Snip20Token { address: "other".to_string(), code_hash: "hash".to_string() };
+    assert_eq!(fee_balance(&deps.storage, "depositor", Some(&other)), Uint128::zero());
+}
+
+
+#[test]
+fn receive_through_encrypted_message() {
+    let mut deps = mock_dependencies();
+    encrypted_test_setup(&mut deps.storage);
+
+    let builder = encrypted_test_builder();
+    let user = builder.signing_pubkey();
+    let user = crate::crypto::pubkey_to_address(&user, crate::test_utils::SIGN_HRP).unwrap();
+    let token = Snip20Token { address: user.clone(), code_hash: "hash".to_string() };
+    register_fee_tokens(&deps.api, &mut deps.storage, &mock_env(), vec![token.clone()]).unwrap();
+
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    let (_, info, context) = handle_encrypted_wrapper_with_context(
+        &deps.api, 
+        &mut deps.storage, 
+        &mock_env(),
+        mock_info("relayer", &[]), 
+        GatewayExecuteMsg::<Option<cosmwasm_std::Empty>>::from(
+            builder.build(&encrypted_test_payload(&builder, &inner)).unwrap()
+        )
+    ).unwrap();
+
+    // the sender is only the remote address of the user
+    assert!(handle_receive_fees(
+        &mut deps.storage, &info.sender, "depositor", Uint128::new(10), None, context.as_ref()
+    ).is_err());
+    assert_eq!(fee_balance(&deps.storage, "depositor", Some(&token)), Uint128::zero());
+}
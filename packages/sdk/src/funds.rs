@@ -10,6 +10,7 @@ mod shade;
 
 
 #[cfg(feature = "funds")]
+#[allow(clippy::module_inception)]
 pub mod funds {
     use cosmwasm_schema::{cw_serde, schemars::JsonSchema};
     use cosmwasm_std::CustomMsg;
@@ -40,6 +41,7 @@ pub mod funds {
 
 
 #[cfg(not(feature = "funds"))]
+#[allow(clippy::module_inception)]
 pub mod funds {
     use cosmwasm_std::Empty;
     pub type FundForwarding = Option<Empty>;
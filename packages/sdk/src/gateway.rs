@@ -1,6 +1,6 @@
-use crate::CosmosAuthData;
+use crate::{fees::Snip20Token, types::Expiration, AssociatedData, Cipher, CosmosAuthData, CosmosCredential, Credential, EncryptedParams};
 use cosmwasm_schema::{cw_serde, schemars::JsonSchema, serde::Serialize};
-use cosmwasm_std::{Binary, Empty};
+use cosmwasm_std::{Binary, Empty, Uint128};
 use secret_toolkit::permit::Permit;
 
 
@@ -11,6 +11,75 @@ pub enum GatewayExecuteMsg<E = Option<Empty>>
 {
     ResetEncryptionKey  { },
 
+    /// removes expired nonces of encrypted messages
+    PruneNonces {
+        limit               :   Option<u32>,
+    },
+
+    /// registers a short-lived key authorising messages on behalf of the sender
+    /// the credential must be the sender address signed with the session key
+    CreateSessionKey {
+        credential          :   CosmosCredential,
+        expires             :   Expiration,
+        allowed             :   Option<Vec<String>>,
+    },
+
+    RevokeSessionKey { },
+
+    /// links credentials to the account of the sender
+    /// every credential must sign the `LinkAccountMessage` with the given nonce
+    LinkCredentials {
+        credentials         :   Vec<Credential>,
+        nonce               :   String,
+    },
+
+    /// removes credentials with the given ids from the account of the sender
+    UnlinkCredentials {
+        ids                 :   Vec<Binary>,
+    },
+
+    /// stops accepting credentials with the given ids. Allowed for the credential
+    /// signing the message, credentials linked to the sender and admins
+    RevokeCredentials {
+        ids                 :   Vec<Binary>,
+    },
+
+    /// makes revoked credentials usable again. Admin only
+    RestoreCredentials {
+        ids                 :   Vec<Binary>,
+    },
+
+    /// deposits the attached uscrt for paying relayer fees
+    /// of the recipient or of the sender if not provided
+    DepositFees {
+        recipient           :   Option<String>,
+    },
+
+    /// sends the deposited fees of the sender back to the recipient
+    /// or to the sender if not provided. The whole deposit if the amount isn't provided
+    WithdrawFees {
+        token               :   Option<Snip20Token>,
+        amount              :   Option<Uint128>,
+        recipient           :   Option<String>,
+    },
+
+    /// grants a fee allowance of the gateway to the feegrant wallet of the sender
+    /// generating the wallet first if needed
+    RequestFeegrant { },
+
+    /// revokes the active fee allowance of the own feegrant wallet of the sender
+    /// so that a new wallet is generated on the next request
+    RevokeFeegrant { },
+
+    /// SNIP-20 receiver interface for depositing tokens for relayer fees
+    Receive {
+        sender              :   String,
+        from                :   String,
+        amount              :   Uint128,
+        memo                :   Option<String>,
+        msg                 :   Option<Binary>,
+    },
+
 
     /// encrypted variant of this enum except for this variant itself 
     Encrypted {
@@ -19,6 +88,10 @@ pub enum GatewayExecuteMsg<E = Option<Empty>>
         payload_hash        :   Binary,
         user_key            :   Binary,
         nonce               :   Binary,
+        epoch               :   Option<u32>,
+        version             :   Option<u8>,
+        cipher              :   Option<Cipher>,
+        associated_data     :   Option<AssociatedData>,
     },
 
     Extension {
@@ -28,6 +101,24 @@ pub enum GatewayExecuteMsg<E = Option<Empty>>
 
 
 
+impl<E: JsonSchema> From<EncryptedParams> for GatewayExecuteMsg<E> {
+    fn from(params: EncryptedParams) -> Self {
+        GatewayExecuteMsg::Encrypted {
+            payload             :   params.payload,
+            payload_signature   :   params.payload_signature,
+            payload_hash        :   params.payload_hash,
+            user_key            :   params.user_key,
+            nonce               :   params.nonce,
+            epoch               :   params.epoch,
+            version             :   params.version,
+            cipher              :   params.cipher,
+            associated_data     :   params.associated_data,
+        }
+    }
+}
+
+
+
 
 #[cw_serde]
 pub enum GatewayQueryMsg<I = Binary, A = CosmosAuthData, E = Option<Empty>> 
@@ -36,6 +127,26 @@ pub enum GatewayQueryMsg<I = Binary, A = CosmosAuthData, E = Option<Empty>>
 
     EncryptionKey  {},
 
+    /// ids of revoked credentials
+    RevokedCredentials {
+        page         :   Option<u32>,
+        page_size    :   Option<u32>,
+    },
+
+
+    /// encrypted variant of the inner query with the response
+    /// encrypted back to the user_key
+    Encrypted {
+        payload             :   Binary,
+        payload_signature   :   Binary,
+        payload_hash        :   Binary,
+        user_key            :   Binary,
+        nonce               :   Binary,
+        epoch               :   Option<u32>,
+        version             :   Option<u8>,
+        cipher              :   Option<Cipher>,
+        associated_data     :   Option<AssociatedData>,
+    },
 
     WithAuthData {
         auth_data    :   A,
@@ -52,3 +163,23 @@ pub enum GatewayQueryMsg<I = Binary, A = CosmosAuthData, E = Option<Empty>>
         query        :   E
     },
 }
+
+
+
+impl<I, A, E> From<EncryptedParams> for GatewayQueryMsg<I, A, E> 
+    where  I: JsonSchema + Clone + Serialize, A: JsonSchema, E: JsonSchema
+{
+    fn from(params: EncryptedParams) -> Self {
+        GatewayQueryMsg::Encrypted {
+            payload             :   params.payload,
+            payload_signature   :   params.payload_signature,
+            payload_hash        :   params.payload_hash,
+            user_key            :   params.user_key,
+            nonce               :   params.nonce,
+            epoch               :   params.epoch,
+            version             :   params.version,
+            cipher              :   params.cipher,
+            associated_data     :   params.associated_data,
+        }
+    }
+}
@@ -1,22 +1,256 @@
 use std::fmt::Display;
 
-use cosmwasm_std::{ensure, Api, StdError, StdResult};
+use cosmwasm_std::{ensure, from_binary, Api, Binary, Env, StdError, StdResult, Storage};
+use secret_toolkit::crypto::sha_256;
 
 use crate::{
-    crypto::{verify_arbitrary, pubkey_to_address}, 
-    CosmosAuthData, CosmosCredential
+    crypto::{
+        verify_arbitrary, verify_eth_personal_sign, verify_eth_typed_data, verify_passkey, verify_ed25519,
+        verify_multisig_arbitrary, multisig_to_address, multisig_pubkey_to_amino,
+        pubkey_to_address_with_scheme, pubkey_to_passkey_address, pubkey_to_ed25519_address, key_set_to_address,
+        eip712::EncryptedTypedData, utils::normalize_pubkey
+    }, 
+    AddressScheme, CosmosAuthData, CosmosCredential, Credential, CredentialKind, Ed25519AddressFormat,
+    Ed25519Credential, EncryptedParams, EncryptedPayload, EthCredential, MultisigCredential, PasskeyCredential
 };
 
 
 impl<M : Display> CosmosCredential<M> {
 
     pub fn address(&self, _ : &dyn Api) -> StdResult<String> {
-        let addr = pubkey_to_address(&self.pubkey, &self.hrp)?;
+        let addr = pubkey_to_address_with_scheme(&self.pubkey, &self.hrp, &self.address_scheme())?;
         Ok(addr)
     }
 
+    pub fn address_scheme(&self) -> AddressScheme {
+        self.scheme.clone().unwrap_or_else(|| AddressScheme::from_hrp(&self.hrp))
+    }
+
+    pub fn id(&self) -> Vec<u8> {
+        normalize_pubkey(&self.pubkey)
+    }
+}
+
+
+
+impl<M : Display> EthCredential<M> {
+
+    pub fn address(&self, _ : &dyn Api) -> StdResult<String> {
+        Ok(self.address.to_lowercase())
+    }
+
+    pub fn id(&self) -> Vec<u8> {
+        self.address.to_lowercase().into_bytes()
+    }
+}
+
+
+
+impl<M : Display> Ed25519Credential<M> {
+
+    pub fn address(&self, _ : &dyn Api) -> StdResult<String> {
+        pubkey_to_ed25519_address(&self.pubkey, &self.address_format())
+    }
+
+    pub fn address_format(&self) -> Ed25519AddressFormat {
+        self.format.clone().unwrap_or(Ed25519AddressFormat::Base58)
+    }
+
+    pub fn id(&self) -> Vec<u8> {
+        normalize_pubkey(&self.pubkey)
+    }
+}
+
+
+
+impl<M : Display> MultisigCredential<M> {
+
+    pub fn address(&self, _ : &dyn Api) -> StdResult<String> {
+        multisig_to_address(self.threshold, &self.pubkeys, &self.hrp)
+    }
+
+    /// Amino encoded multisig public key
+    pub fn id(&self) -> Vec<u8> {
+        multisig_pubkey_to_amino(self.threshold, &self.pubkeys).unwrap_or_default()
+    }
+}
+
+
+
+impl Ed25519AddressFormat {
+    /// Format of the given address. Hex if it consists of 64 hex characters
+    pub fn from_address(address: &str) -> Self {
+        if address.len() == 64 && address.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ed25519AddressFormat::Hex
+        } else {
+            Ed25519AddressFormat::Base58
+        }
+    }
+}
+
+
+
+impl PasskeyCredential {
+
+    pub fn address(&self, _ : &dyn Api) -> StdResult<String> {
+        pubkey_to_passkey_address(&self.assertion.credential_id, &self.pubkey, &self.hrp)
+    }
+
     pub fn id(&self) -> Vec<u8> {
-        self.pubkey.0.clone()
+        normalize_pubkey(&self.pubkey)
+    }
+}
+
+
+
+impl<M : Display> Credential<M> {
+
+    pub fn address(&self, api : &dyn Api) -> StdResult<String> {
+        match self {
+            Credential::CosmosArbitrary(c) => c.address(api),
+            Credential::EthPersonalSign(c) => c.address(api),
+            Credential::EthTypedData(c) => c.address(api),
+            Credential::Passkey(c) => c.address(api),
+            Credential::Ed25519(c) => c.address(api),
+            Credential::AminoMultisig(c) => c.address(api),
+        }
+    }
+
+    pub fn id(&self) -> Vec<u8> {
+        match self {
+            Credential::CosmosArbitrary(c) => c.id(),
+            Credential::EthPersonalSign(c) => c.id(),
+            Credential::EthTypedData(c) => c.id(),
+            Credential::Passkey(c) => c.id(),
+            Credential::Ed25519(c) => c.id(),
+            Credential::AminoMultisig(c) => c.id(),
+        }
+    }
+
+    /// Whether the credential carries the given message as the signed one.
+    /// Typed data describes encrypted params and never matches
+    pub fn signs(&self, message: &str) -> bool {
+        match self {
+            Credential::CosmosArbitrary(c) => c.message.to_string() == message,
+            Credential::EthPersonalSign(c) => c.message.to_string() == message,
+            Credential::EthTypedData(_) => false,
+            Credential::Passkey(c) => c.message.as_slice() == message.as_bytes(),
+            Credential::Ed25519(c) => c.message.to_string() == message,
+            Credential::AminoMultisig(c) => c.message.to_string() == message,
+        }
+    }
+
+    /// Verifies the signature and returns the address of the signer.
+    /// Revoked credentials are rejected
+    pub fn verify(&self, api : &dyn Api, storage: &dyn Storage) -> StdResult<String> {
+        match self {
+            Credential::CosmosArbitrary(c) => verify_arbitrary(api, storage, c),
+            Credential::EthPersonalSign(c) => verify_eth_personal_sign(api, storage, c),
+            Credential::EthTypedData(c) => verify_eth_typed_data(api, storage, c),
+            Credential::Passkey(c) => verify_passkey(api, storage, c),
+            Credential::Ed25519(c) => verify_ed25519(api, storage, c),
+            Credential::AminoMultisig(c) => verify_multisig_arbitrary(api, storage, c),
+        }
+    }
+}
+
+
+
+impl<M : Display> From<CosmosCredential<M>> for Credential<M> {
+    fn from(cred: CosmosCredential<M>) -> Self {
+        Credential::CosmosArbitrary(cred)
+    }
+}
+
+
+impl<M : Display> From<EthCredential<M>> for Credential<M> {
+    fn from(cred: EthCredential<M>) -> Self {
+        Credential::EthPersonalSign(cred)
+    }
+}
+
+
+impl<M : Display> From<MultisigCredential<M>> for Credential<M> {
+    fn from(cred: MultisigCredential<M>) -> Self {
+        Credential::AminoMultisig(cred)
+    }
+}
+
+
+impl<M : Display> From<Ed25519Credential<M>> for Credential<M> {
+    fn from(cred: Ed25519Credential<M>) -> Self {
+        Credential::Ed25519(cred)
+    }
+}
+
+
+
+impl EncryptedPayload {
+
+    /// Type of the credential declared in the payload.
+    /// Addresses starting with `0x` are treated as Ethereum wallets using personal_sign by default
+    pub fn credential_kind(&self) -> CredentialKind {
+        match &self.kind {
+            Some(kind) => kind.clone(),
+            None if self.user_address.starts_with("0x") => CredentialKind::EthPersonalSign,
+            None => CredentialKind::CosmosArbitrary,
+        }
+    }
+
+    /// Whether the address derived from the credential is the one declared in the payload.
+    /// Ethereum addresses are compared case-insensitively
+    pub fn declares_address(&self, address: &str) -> bool {
+        if self.user_address.starts_with("0x") {
+            self.user_address.eq_ignore_ascii_case(address)
+        } else {
+            self.user_address == address
+        }
+    }
+
+    /// Credential of the user who signed the hash of the encrypted payload.
+    /// For passkeys the signature of the params is a JSON encoded [crate::PasskeyAssertion]
+    pub fn credential(&self, env: &Env, params: &EncryptedParams) -> StdResult<Credential<Binary>> {
+        let pubkey = if self.user_pubkey.is_empty() { None } else { Some(self.user_pubkey.clone()) };
+
+        let credential = match self.credential_kind() {
+            CredentialKind::CosmosArbitrary => CosmosCredential {
+                pubkey      :   self.user_pubkey.clone(),
+                signature   :   params.payload_signature.clone(),
+                message     :   params.payload_hash.clone(),
+                hrp         :   self.hrp.clone(),
+                scheme      :   self.scheme.clone(),
+            }.into(),
+
+            CredentialKind::EthPersonalSign => EthCredential {
+                address     :   self.user_address.clone(),
+                signature   :   params.payload_signature.clone(),
+                message     :   params.payload_hash.clone(),
+                pubkey,
+            }.into(),
+
+            CredentialKind::EthTypedData => Credential::EthTypedData(EthCredential {
+                address     :   self.user_address.clone(),
+                signature   :   params.payload_signature.clone(),
+                message     :   EncryptedTypedData::new(params, env),
+                pubkey,
+            }),
+
+            CredentialKind::Passkey => Credential::Passkey(PasskeyCredential {
+                pubkey      :   self.user_pubkey.clone(),
+                assertion   :   from_binary(&params.payload_signature)?,
+                message     :   params.payload_hash.clone(),
+                hrp         :   self.hrp.clone(),
+            }),
+
+            CredentialKind::Ed25519 => Ed25519Credential {
+                pubkey      :   self.user_pubkey.clone(),
+                signature   :   params.payload_signature.clone(),
+                message     :   params.payload_hash.clone(),
+                format      :   Some(Ed25519AddressFormat::from_address(&self.user_address)),
+            }.into(),
+        };
+
+        Ok(credential)
     }
 }
 
@@ -30,36 +264,85 @@ impl CosmosAuthData {
         if let Some(i) = self.primary_index {
             ensure!(i < length as u8, StdError::generic_err("Primary index is out of bounds"));
         }
+        if let Some(threshold) = self.threshold {
+            ensure!(
+                threshold > 0 && threshold as usize <= length, 
+                StdError::generic_err("Threshold must be between 1 and the number of credentials")
+            );
+            ensure!(
+                self.primary_index.is_none(),
+                StdError::generic_err("Primary index can't be used with a threshold")
+            );
+            let mut ids = self.ids();
+            ids.sort();
+            ids.dedup();
+            ensure!(ids.len() == length, StdError::generic_err("Credentials must be unique"));
+        }
         Ok(())
     }
 
-    pub fn verify(&self, api: &dyn Api) -> StdResult<()> {
+    /// Verifies all the credentials or at least `threshold` of them if provided.
+    /// Revoked credentials are never considered valid
+    pub fn verify(&self, api: &dyn Api, storage: &dyn Storage) -> StdResult<()> {
         self.validate()?;
-        self.credentials
-            .iter()
-            .map(|c| verify_arbitrary(api, c))
-            .collect::<StdResult<Vec<String>>>()?;
+        match self.threshold {
+            None => {
+                self.credentials
+                    .iter()
+                    .map(|c| c.verify(api, storage))
+                    .collect::<StdResult<Vec<String>>>()?;
+            },
+            Some(threshold) => {
+                let valid = self.credentials
+                    .iter()
+                    .filter(|c| c.verify(api, storage).is_ok())
+                    .count();
+                ensure!(
+                    valid >= threshold as usize,
+                    StdError::generic_err(format!("Expected at least {} valid credentials, got {}", threshold, valid))
+                );
+            }
+        }
         Ok(())
     }
 
-    pub fn primary(&self) -> CosmosCredential {
+    pub fn primary(&self) -> Credential {
         match self.primary_index {
             Some(i) => self.credentials[i as usize].clone(),
             None => self.credentials[0].clone(),
         }
     }
 
-    pub fn primary_id(&self) -> Vec<u8> {
-        self.primary().id()
+    /// Identifier of the account controlled by a threshold of credentials. Hash of the threshold
+    /// and the sorted ids of all the credentials that doesn't depend on their order
+    pub fn key_set_id(&self) -> Option<Vec<u8>> {
+        let threshold = self.threshold?;
+        let mut ids = self.ids();
+        ids.sort();
+
+        let mut preimage = vec![threshold];
+        for id in ids {
+            preimage.extend_from_slice(&(id.len() as u16).to_be_bytes());
+            preimage.extend_from_slice(&id);
+        }
+        Some(sha_256(&preimage).to_vec())
     }
 
+    /// Id of the primary credential or of the whole key set if a threshold is used
+    pub fn primary_id(&self) -> Vec<u8> {
+        self.key_set_id().unwrap_or_else(|| self.primary().id())
+    }
 
+    /// Address of the primary credential or of the whole key set if a threshold is used
     pub fn primary_address(&self, api: &dyn Api) -> StdResult<String> {
-        self.primary().address(api)
+        match self.key_set_id() {
+            Some(id) => key_set_to_address(&id),
+            None => self.primary().address(api)
+        }
     }
 
 
-    pub fn secondaries(&self) -> Vec<CosmosCredential> {
+    pub fn secondaries(&self) -> Vec<Credential> {
         match self.primary_index {
             None => self.credentials[1..].to_vec(),
             Some(i) => self.credentials
@@ -3,16 +3,28 @@ pub mod crypto;
 pub mod common;
 pub mod traits;
 pub mod funds;
+pub mod session_key;
+pub mod accounts;
+pub mod revocation;
+pub mod fees;
+pub mod errors;
 mod inner;
 
+#[cfg(test)]
+mod test_utils;
+
+#[cfg(feature = "wallets")]
+pub mod feegrant;
+
 
 #[cfg(feature = "gateway")]
 pub mod gateway;
 
 
 use std::fmt::Display;
-use cosmwasm_schema::cw_serde;
+use cosmwasm_schema::{cw_serde, serde::{Deserialize, Deserializer}};
 use cosmwasm_std::Binary;
+use types::Expiration;
 
 
 
@@ -30,7 +42,158 @@ pub struct CosmosCredential<M = String>
     /// signed inner message before being wrapped with 036
     pub message   :   M,
     /// prefix for the bech32 address on remote cosmos chain
-    pub hrp       :   String
+    pub hrp       :   String,
+    /// scheme for deriving the address from the public key
+    /// chosen based on the prefix if not provided
+    pub scheme    :   Option<AddressScheme>,
+}
+
+
+
+
+/// Credential of an Ethereum wallet signing a message with EIP-191 `personal_sign`
+#[cw_serde]
+pub struct EthCredential<M = String> 
+    where M: Display
+{
+    /// 0x prefixed hex encoded address of the signer
+    pub address   :   String,
+    /// 65 bytes signature (r, s, v) over keccak256 digest of the prefixed message
+    pub signature :   Binary,
+    /// signed inner message before being prefixed with EIP-191 preamble
+    pub message   :   M,
+    /// public key of the signer. Recovered from the signature if not provided
+    pub pubkey    :   Option<Binary>,
+}
+
+
+
+
+/// Credential of a wallet signing raw messages with an ed25519 key e.g. Solana or Near wallets
+#[cw_serde]
+pub struct Ed25519Credential<M = String> 
+    where M: Display
+{
+    /// 32 bytes ed25519 public key of the signer
+    pub pubkey    :   Binary,
+    /// 64 bytes ed25519 signature over the message
+    pub signature :   Binary,
+    /// signed message
+    pub message   :   M,
+    /// encoding of the public key used as the address. Base58 if not provided
+    pub format    :   Option<Ed25519AddressFormat>,
+}
+
+
+
+
+/// Credential of an amino multisig account (`LegacyAminoPubKey`) with members
+/// signing an arbitrary message (036) on behalf of the multisig address
+#[cw_serde]
+pub struct MultisigCredential<M = String> 
+    where M: Display
+{
+    /// minimal number of members that must sign the message
+    pub threshold   :   u8,
+    /// compressed secp256k1 public keys of the members in the order of the multisig
+    pub pubkeys     :   Vec<Binary>,
+    /// signatures of the members at the same positions as their public keys
+    pub signatures  :   Vec<Option<Binary>>,
+    /// signed inner message before being wrapped with 036
+    pub message     :   M,
+    /// prefix for the bech32 address of the multisig
+    pub hrp         :   String,
+}
+
+
+
+
+/// Assertion returned by a WebAuthn authenticator for `navigator.credentials.get`
+#[cw_serde]
+pub struct PasskeyAssertion {
+    /// id of the credential assigned by the authenticator
+    pub credential_id       :   Binary,
+    /// authenticator data of the assertion
+    pub authenticator_data  :   Binary,
+    /// exact bytes of the client data JSON containing the challenge
+    pub client_data_json    :   Binary,
+    /// P-256 signature over the authenticator data and sha256 of the client data
+    /// in DER or 64 bytes (r, s) encoding
+    pub signature           :   Binary,
+}
+
+
+
+
+/// Credential of a device passkey signing WebAuthn challenges with a P-256 key
+#[cw_serde]
+pub struct PasskeyCredential {
+    /// SEC1 encoded P-256 public key of the passkey
+    pub pubkey      :   Binary,
+    /// assertion returned by the authenticator
+    pub assertion   :   PasskeyAssertion,
+    /// bytes that must be used as the challenge of the assertion
+    pub message     :   Binary,
+    /// prefix for the bech32 address derived from the credential
+    pub hrp         :   String,
+}
+
+
+
+
+/// Credential of any supported type
+#[cw_serde]
+pub enum Credential<M = String> 
+    where M: Display
+{
+    /// Cosmos arbitrary (036) message
+    CosmosArbitrary(CosmosCredential<M>),
+    /// Ethereum personal_sign (EIP-191) message
+    EthPersonalSign(EthCredential<M>),
+    /// Ethereum typed data (EIP-712) describing encrypted params
+    EthTypedData(EthCredential<crypto::eip712::EncryptedTypedData>),
+    /// WebAuthn assertion of a device passkey
+    Passkey(PasskeyCredential),
+    /// Raw message signed with an ed25519 key
+    Ed25519(Ed25519Credential<M>),
+    /// Cosmos arbitrary (036) message signed by members of an amino multisig
+    AminoMultisig(MultisigCredential<M>),
+}
+
+
+
+
+/// Scheme for deriving bech32 addresses from secp256k1 public keys
+#[cw_serde]
+pub enum AddressScheme {
+    /// ripemd160(sha256(compressed pubkey)) used by most of the cosmos chains
+    Cosmos,
+    /// last 20 bytes of keccak256(uncompressed pubkey) used by chains with
+    /// `ethsecp256k1` keys like Injective, Evmos or Cronos
+    EthSecp256k1,
+}
+
+
+
+/// Encoding of ed25519 public keys used as addresses
+#[cw_serde]
+pub enum Ed25519AddressFormat {
+    /// base58 encoding used by Solana
+    Base58,
+    /// lowercase hex encoding used by implicit Near accounts
+    Hex,
+}
+
+
+
+/// Type of the credential used for signing the hash of an encrypted payload
+#[cw_serde]
+pub enum CredentialKind {
+    CosmosArbitrary,
+    EthPersonalSign,
+    EthTypedData,
+    Passkey,
+    Ed25519,
 }
 
 
@@ -43,9 +206,37 @@ pub struct CosmosAuthData<M = String>
     where M: Display
 {
     /// Public key corresponding to the user's secret key used for signing.
-    pub credentials    :   Vec<CosmosCredential<M>>,
+    /// Bare cosmos credentials of the older clients are accepted as well
+    #[serde(deserialize_with = "deserialize_credentials", bound(deserialize = "M: Deserialize<'de>"))]
+    pub credentials    :   Vec<Credential<M>>,
     /// Index of the primary credential in the list
     pub primary_index  :   Option<u8>,
+    /// Minimal number of credentials that must be valid. If provided the account
+    /// is controlled by the whole set of credentials instead of the primary one
+    pub threshold      :   Option<u8>,
+}
+
+
+
+/// Credentials used to be cosmos credentials without a tag before other kinds were added
+#[derive(Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde", untagged)]
+enum CredentialShape<M: Display> {
+    Tagged(Credential<M>),
+    Legacy(CosmosCredential<M>),
+}
+
+
+fn deserialize_credentials<'de, D, M>(deserializer: D) -> Result<Vec<Credential<M>>, D::Error>
+    where D: Deserializer<'de>, M: Display + Deserialize<'de>
+{
+    Ok(Vec::<CredentialShape<M>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|shape| match shape {
+            CredentialShape::Tagged(credential) => credential,
+            CredentialShape::Legacy(credential) => Credential::CosmosArbitrary(credential),
+        })
+        .collect())
 }
 
 
@@ -55,13 +246,55 @@ pub struct CosmosAuthData<M = String>
 #[cw_serde]
 pub struct EncryptedPayload {
     /// bech32 prefix address of a wallet used for signing hash of the payload 
+    /// or 0x prefixed address for Ethereum wallets using personal_sign
     pub user_address   :  String,
     /// Public key of a wallet used for signing hash of the payload 
+    /// or P-256 public key of a passkey or ed25519 public key
     pub user_pubkey   :   Binary,
     /// Human readable prefix for the bech32 address on the remote cosmos chain
     pub hrp           :   String,
     /// Plaintext message to be encrypted
     pub msg           :   Binary,
+    /// Type of the credential used for signing the payload hash
+    /// Inferred from the user address if not provided
+    pub kind          :   Option<CredentialKind>,
+    /// Block height or time at which the payload was created.
+    /// Required if the gateway enforces a validity window for nonces
+    pub issued_at     :   Option<Expiration>,
+    /// Scheme for deriving the address of a cosmos wallet from its public key.
+    /// Inferred from the hrp if not provided
+    pub scheme        :   Option<AddressScheme>,
+    /// Fee paid to the relayer from the deposit of the user. See [fees::RelayerFee]
+    pub fee           :   Option<fees::RelayerFee>,
+}
+
+
+
+
+/// AEAD algorithm used for encrypting the payload
+#[cw_serde]
+#[derive(Default)]
+pub enum Cipher {
+    /// ChaCha20-Poly1305 with a 12 bytes nonce
+    #[default]
+    Chacha20Poly1305,
+    /// XChaCha20-Poly1305 with a 24 bytes nonce that is safe to pick at random
+    Xchacha20Poly1305,
+    /// AES-256-GCM-SIV with a 12 bytes nonce resistant to nonce misuse
+    Aes256GcmSiv,
+}
+
+
+
+
+/// Data authenticated together with the encrypted payload without being a part of it
+#[cw_serde]
+pub enum AssociatedData {
+    /// Address of the gateway and the chain id
+    Contract,
+    /// Address of the gateway, the chain id and the address of the relayer
+    /// that must submit the message
+    ContractAndRelayer,
 }
 
 
@@ -71,7 +304,8 @@ pub struct EncryptedPayload {
 pub struct EncryptedParams {
     /// Encrypted payload containging hidden message
     pub payload            :   Binary,
-    /// Sha256 hash of the payload
+    /// Sha256 hash of the payload. Newer protocol versions also hash the nonce and the user key.
+    /// See [crypto::payload_hash]
     pub payload_hash       :   Binary,
     /// Signed base64 digest of the payload_hash being wrapped
     /// in an cosmos arbitrary (036) object and rehashed again with sha256
@@ -81,4 +315,25 @@ pub struct EncryptedParams {
     pub user_key           :   Binary,
     /// One-time nonce used for chacha20_poly1305 encryption
     pub nonce              :   Binary,
+    /// Epoch of the gateway key the payload was encrypted to. 
+    /// The current key is used if not provided
+    pub epoch              :   Option<u32>,
+    /// Version of the encryption protocol defining how the shared key is derived.
+    /// The legacy version using the raw ECDH secret is assumed if not provided
+    pub version            :   Option<u8>,
+    /// Cipher the payload was encrypted with. ChaCha20-Poly1305 is assumed if not provided
+    pub cipher             :   Option<Cipher>,
+    /// Associated data the payload was encrypted with. None is used if not provided
+    pub associated_data    :   Option<AssociatedData>,
+}
+
+
+
+
+#[cw_serde]
+pub struct EncryptedResponse {
+    /// Response data encrypted with the key shared with the user
+    pub data               :   Binary,
+    /// Nonce used for chacha20_poly1305 encryption of the response
+    pub nonce              :   Binary,
 }
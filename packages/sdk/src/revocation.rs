@@ -0,0 +1,146 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_std::{ensure, Binary, BlockInfo, Response, StdError, StdResult, Storage};
+use secret_toolkit::{
+    storage::{Keymap, KeymapBuilder},
+    serialization::Bincode2
+};
+
+use crate::{accounts::linked_account, crypto::utils::normalize_pubkey};
+
+
+
+/// Default number of revoked credentials returned per page
+pub const REVOKED_PAGE_SIZE: u32 = 30;
+
+
+// a mapping of ids of revoked credentials to the height they were revoked at
+#[allow(clippy::declare_interior_mutable_const)]
+pub const REVOKED_CREDENTIALS   :    Keymap<Vec<u8>, u64, Bincode2>
+                                =    KeymapBuilder::new(b"revoked_credentials").build();
+
+
+
+/// Whether the credential with the given id has been revoked. Public keys are compared
+/// in their compressed form
+pub fn is_revoked(
+    storage     :   &dyn Storage,
+    id          :   &[u8],
+) -> bool {
+    REVOKED_CREDENTIALS.contains(storage, &normalize_pubkey(id))
+}
+
+
+
+/// Fails if the credential with the given id has been revoked
+pub fn ensure_not_revoked(
+    storage     :   &dyn Storage,
+    id          :   &[u8],
+) -> StdResult<()> {
+    ensure!(
+        !is_revoked(storage, id),
+        StdError::generic_err("Credential has been revoked")
+    );
+    Ok(())
+}
+
+
+
+/// Whether the sender can revoke the credential with the given id. Allowed if the credential
+/// signed the current message or is linked to the account of the sender
+pub fn can_revoke(
+    storage     :   &dyn Storage,
+    sender      :   &str,
+    signer_id   :   Option<&[u8]>,
+    id          :   &[u8],
+) -> bool {
+    let id = normalize_pubkey(id);
+    signer_id.map(normalize_pubkey) == Some(id.clone()) || linked_account(storage, &id).as_deref() == Some(sender)
+}
+
+
+
+/// Marks the credentials as revoked. Authorisation must be checked by the caller
+pub fn revoke_credentials(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    ids         :   &[Binary],
+) -> StdResult<()> {
+    ensure!(!ids.is_empty(), StdError::generic_err("Ids must not be empty"));
+    for id in ids {
+        if !is_revoked(storage, id) {
+            REVOKED_CREDENTIALS.insert(storage, &normalize_pubkey(id), &block.height)?;
+        }
+    }
+    Ok(())
+}
+
+
+
+/// Makes revoked credentials usable again. Meant to be used by admins only
+pub fn restore_credentials(
+    storage     :   &mut dyn Storage,
+    ids         :   &[Binary],
+) -> StdResult<()> {
+    for id in ids {
+        REVOKED_CREDENTIALS.remove(storage, &normalize_pubkey(id))?;
+    }
+    Ok(())
+}
+
+
+
+/// Returns a page of revoked credential ids
+pub fn revoked_credentials(
+    storage     :   &dyn Storage,
+    page        :   Option<u32>,
+    page_size   :   Option<u32>,
+) -> StdResult<Vec<Binary>> {
+    Ok(REVOKED_CREDENTIALS
+        .paging_keys(storage, page.unwrap_or(0), page_size.unwrap_or(REVOKED_PAGE_SIZE))?
+        .into_iter()
+        .map(Binary)
+        .collect())
+}
+
+
+
+/// Revokes the credentials if the sender is an admin or controls every one of them
+/// according to [can_revoke]
+pub fn handle_revoke_credentials(
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    sender      :   &str,
+    signer_id   :   Option<&[u8]>,
+    is_admin    :   bool,
+    ids         :   Vec<Binary>,
+) -> StdResult<Response> {
+    ensure!(
+        is_admin || ids.iter().all(|id| can_revoke(storage, sender, signer_id, id)),
+        StdError::generic_err("Only the credential itself, linked credentials or an admin can revoke it")
+    );
+    revoke_credentials(storage, block, &ids)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "revoke_credentials")
+      .add_attribute("revoked", ids.len().to_string())
+    )
+}
+
+
+
+pub fn handle_restore_credentials(
+    storage     :   &mut dyn Storage,
+    ids         :   Vec<Binary>,
+) -> StdResult<Response> {
+    restore_credentials(storage, &ids)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "restore_credentials")
+      .add_attribute("restored", ids.len().to_string())
+    )
+}
+
+
+#[cfg(test)]
+mod tests;
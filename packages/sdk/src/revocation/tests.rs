@@ -0,0 +1,153 @@
+use cosmwasm_std::{
+    testing::{mock_dependencies, mock_env, mock_info},
+    Api, Binary, Empty, Storage
+};
+use crate::{
+    common::handle_encrypted_wrapper,
+    crypto::{client::sign_arbitrary, pubkey_to_address, utils::secret_key_from_bytes, verify_arbitrary},
+    gateway::GatewayExecuteMsg,
+    test_utils::{encrypted_test_builder, encrypted_test_payload, encrypted_test_setup, SIGN_HRP, USER_PRIVATE},
+    AddressScheme, CosmosAuthData, CosmosCredential
+};
+use super::*;
+
+
+const MESSAGE : &str = "Hello, world";
+
+
+
+/// Public key of the test user of [encrypted_test_builder]
+fn user_id() -> Binary {
+    encrypted_test_builder().signing_pubkey()
+}
+
+
+/// Whether a message of the test user is accepted by the gateway
+fn accepted(api: &dyn Api, storage: &mut dyn Storage) -> bool {
+    let builder = encrypted_test_builder();
+    let inner : GatewayExecuteMsg = GatewayExecuteMsg::Extension { msg: None };
+    handle_encrypted_wrapper(
+        api,
+        storage,
+        &mock_env(),
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<Option<Empty>>::from(builder.build(&encrypted_test_payload(&builder, &inner)).unwrap())
+    ).is_ok()
+}
+
+
+/// Credential of the test user signing [MESSAGE] with the given encoding of its public key
+fn user_credential(pubkey: Binary) -> CosmosCredential {
+    let signer = pubkey_to_address(&pubkey, SIGN_HRP).unwrap();
+    CosmosCredential {
+        signature: sign_arbitrary(
+            &secret_key_from_bytes(&USER_PRIVATE.to_vec()).unwrap(),
+            &signer,
+            MESSAGE,
+            &AddressScheme::Cosmos
+        ),
+        message: MESSAGE.to_string(),
+        pubkey,
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
+    }
+}
+
+
+fn uncompressed(id: &Binary) -> Binary {
+    Binary(secp256k1::PublicKey::from_slice(id).unwrap().serialize_uncompressed().to_vec())
+}
+
+
+
+#[test]
+fn strangers_cannot_revoke() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let id = user_id();
+
+    assert!(handle_revoke_credentials(
+        &mut deps.storage, &env.block, "cosmos1other", None, false, vec![id.clone()]
+    ).is_err());
+    assert!(!is_revoked(&deps.storage, &id));
+}
+
+
+#[test]
+fn signer_revokes_own_key() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+    let id = user_id();
+    let sender = pubkey_to_address(&id, SIGN_HRP).unwrap();
+    assert!(accepted(&deps.api, &mut deps.storage));
+
+    handle_revoke_credentials(
+        &mut deps.storage, &env.block, &sender, Some(&id), false, vec![id.clone()]
+    ).unwrap();
+    assert_eq!(revoked_credentials(&deps.storage, None, None).unwrap(), vec![id]);
+    assert!(!accepted(&deps.api, &mut deps.storage));
+}
+
+
+#[test]
+fn admin_revokes_any_credential() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let id = user_id();
+
+    handle_revoke_credentials(
+        &mut deps.storage, &env.block, "admin", None, true, vec![id.clone()]
+    ).unwrap();
+    assert!(is_revoked(&deps.storage, &id));
+}
+
+
+#[test]
+fn revoked_credentials_in_auth_data() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let id = user_id();
+    revoke_credentials(&mut deps.storage, &env.block, std::slice::from_ref(&id)).unwrap();
+
+    let auth = CosmosAuthData {
+        credentials: vec![user_credential(id).into()],
+        primary_index: None,
+        threshold: None,
+    };
+    assert!(auth.verify(&deps.api, &deps.storage).is_err());
+    assert!(CosmosAuthData { threshold: Some(1), ..auth }.verify(&deps.api, &deps.storage).is_err());
+}
+
+
+#[test]
+fn revoked_uncompressed_key() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let id = user_id();
+    revoke_credentials(&mut deps.storage, &env.block, std::slice::from_ref(&id)).unwrap();
+
+    let credential = user_credential(uncompressed(&id));
+    assert!(is_revoked(&deps.storage, &credential.pubkey));
+    assert!(verify_arbitrary(&deps.api, &deps.storage, &credential).is_err());
+}
+
+
+#[test]
+fn restored_credentials() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+    let id = user_id();
+    revoke_credentials(&mut deps.storage, &env.block, std::slice::from_ref(&id)).unwrap();
+
+    restore_credentials(&mut deps.storage, std::slice::from_ref(&id)).unwrap();
+    let auth = CosmosAuthData {
+        credentials: vec![user_credential(id.clone()).into()],
+        primary_index: None,
+        threshold: None,
+    };
+    auth.verify(&deps.api, &deps.storage).unwrap();
+    verify_arbitrary(&deps.api, &deps.storage, &user_credential(uncompressed(&id))).unwrap();
+    assert!(accepted(&deps.api, &mut deps.storage));
+}
@@ -0,0 +1,267 @@
+#![allow(clippy::borrow_interior_mutable_const)]
+
+use cosmwasm_schema::{cw_serde, serde::Serialize};
+use cosmwasm_std::{ensure, to_vec, Api, Binary, BlockInfo, Response, StdError, StdResult, Storage};
+use secret_toolkit::{
+    storage::{Item, Keymap, KeymapBuilder, WithoutIter},
+    serialization::Bincode2
+};
+
+use crate::{crypto::{utils::normalize_pubkey, verify_arbitrary}, revocation::ensure_not_revoked, types::{Duration, Expiration, WEEK}, CosmosCredential};
+
+
+
+/// Maximal lifetime of a session key used if the gateway doesn't configure its own
+pub const DEFAULT_MAX_SESSION_DURATION: Duration = WEEK;
+
+
+
+/// Short-lived key that can authorise messages on behalf of its owner
+#[cw_serde]
+pub struct SessionKey {
+    /// Address of the account that registered the key
+    pub owner       :   String,
+    /// Public key of the session wallet
+    pub pubkey      :   Binary,
+    /// Moment after which the key can no longer be used
+    pub expires     :   Expiration,
+    /// Names of the message variants the key is allowed to authorise.
+    /// Any variant that can be authorised by a session key if not specified
+    pub allowed     :   Option<Vec<String>>,
+}
+
+
+
+// a mapping of session public keys to the respective session keys
+#[allow(clippy::declare_interior_mutable_const)]
+pub const SESSION_KEYS          :    Keymap<Vec<u8>, SessionKey, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"session_keys").without_iter().build();
+
+// a mapping of owner addresses to the public key of their active session key
+#[allow(clippy::declare_interior_mutable_const)]
+pub const SESSION_OWNERS        :    Keymap<String, Vec<u8>, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"session_owners").without_iter().build();
+
+// a mapping of session public keys to the ids of the credentials that authorised their creation
+#[allow(clippy::declare_interior_mutable_const)]
+pub const SESSION_OWNER_IDS     :    Keymap<Vec<u8>, Vec<u8>, Bincode2, WithoutIter>
+                                =    KeymapBuilder::new(b"session_owner_ids").without_iter().build();
+
+// maximal time or number of blocks from the creation of a session key until its expiration
+pub const MAX_SESSION_DURATION  :    Item<Duration>   =    Item::new(b"session_max_duration");
+
+
+
+/// Sets for how long session keys can stay valid. Passing `None` restores [DEFAULT_MAX_SESSION_DURATION]
+pub fn set_max_session_duration(
+    storage     :   &mut dyn Storage,
+    duration    :   Option<Duration>,
+) -> StdResult<()> {
+    match duration {
+        Some(duration) => MAX_SESSION_DURATION.save(storage, &duration),
+        None => {
+            MAX_SESSION_DURATION.remove(storage);
+            Ok(())
+        }
+    }
+}
+
+
+
+/// Returns the name of an externally tagged enum variant e.g. `store_secret` for `StoreSecret { .. }`
+pub fn variant_name<T: Serialize>(msg: &T) -> Option<String> {
+    let json = String::from_utf8(to_vec(msg).ok()?).ok()?;
+    let name = match json.strip_prefix("{\"") {
+        Some(rest) => rest.split('"').next()?,
+        None => json.strip_prefix('"')?.strip_suffix('"')?,
+    };
+    Some(name.to_string())
+}
+
+
+
+impl SessionKey {
+
+    pub fn is_allowed(&self, variant: &Option<String>) -> bool {
+        match (variant, &self.allowed) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(variant), Some(allowed)) => allowed.contains(variant),
+        }
+    }
+}
+
+
+
+/// Registers a new session key for the owner replacing the previous one.
+/// The credential must be a signature of the owner address made with the session key.
+/// The key must expire within the maximal session duration measured in the same units.
+/// The key stops working if the credential of the owner with the given id gets revoked
+#[allow(clippy::too_many_arguments)]
+pub fn create_session_key(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    owner       :   &str,
+    owner_id    :   Option<&[u8]>,
+    credential  :   CosmosCredential,
+    expires     :   Expiration,
+    allowed     :   Option<Vec<String>>,
+) -> StdResult<SessionKey> {
+    ensure!(
+        credential.message == owner,
+        StdError::generic_err("Session key must sign the address of the owner")
+    );
+    verify_arbitrary(api, storage, &credential)?;
+
+    ensure!(
+        !matches!(expires, Expiration::Never {}),
+        StdError::generic_err("Session key must have an expiration")
+    );
+    ensure!(
+        !expires.is_expired(block),
+        StdError::generic_err("Session key is already expired")
+    );
+    let max = MAX_SESSION_DURATION.may_load(storage)?.unwrap_or(DEFAULT_MAX_SESSION_DURATION);
+    ensure!(
+        expires <= max.after(block),
+        StdError::generic_err(format!("Session key must expire within {}", max))
+    );
+
+    let pubkey = credential.id();
+    ensure!(
+        !SESSION_KEYS.contains(storage, &pubkey),
+        StdError::generic_err("Session key is already registered")
+    );
+
+    revoke_session_key(storage, owner)?;
+
+    let session = SessionKey {
+        owner: owner.to_string(),
+        pubkey: credential.pubkey,
+        expires,
+        allowed,
+    };
+
+    SESSION_KEYS.insert(storage, &pubkey, &session)?;
+    SESSION_OWNERS.insert(storage, &owner.to_string(), &pubkey)?;
+    if let Some(owner_id) = owner_id {
+        SESSION_OWNER_IDS.insert(storage, &pubkey, &normalize_pubkey(owner_id))?;
+    }
+
+    Ok(session)
+}
+
+
+
+/// Removes an active session key of the owner if there is any
+pub fn revoke_session_key(
+    storage     :   &mut dyn Storage,
+    owner       :   &str,
+) -> StdResult<()> {
+    let owner = owner.to_string();
+    if let Some(pubkey) = SESSION_OWNERS.get(storage, &owner) {
+        SESSION_KEYS.remove(storage, &pubkey)?;
+        SESSION_OWNER_IDS.remove(storage, &pubkey)?;
+        SESSION_OWNERS.remove(storage, &owner)?;
+    }
+    Ok(())
+}
+
+
+
+/// Returns the session key with the given public key or `None` if there is no such key.
+/// Fails if the session key is expired or either it or the credential of its owner is revoked
+pub fn session_key(
+    storage     :   &dyn Storage,
+    block       :   &BlockInfo,
+    pubkey      :   &[u8],
+) -> StdResult<Option<SessionKey>> {
+    let pubkey = normalize_pubkey(pubkey);
+    let session = match SESSION_KEYS.get(storage, &pubkey) {
+        Some(session) => session,
+        None => return Ok(None)
+    };
+    ensure!(
+        !session.expires.is_expired(block),
+        StdError::generic_err("Session key is expired")
+    );
+    ensure_not_revoked(storage, &pubkey)?;
+    if let Some(owner_id) = SESSION_OWNER_IDS.get(storage, &pubkey) {
+        ensure_not_revoked(storage, &owner_id)?;
+    }
+    Ok(Some(session))
+}
+
+
+
+/// Fails if the session key isn't allowed to authorise all the given variants
+pub fn ensure_session_allowed(
+    session     :   &SessionKey,
+    variants    :   &[Option<String>],
+) -> StdResult<()> {
+    ensure!(
+        variants.iter().all(|variant| session.is_allowed(variant)),
+        StdError::generic_err("Session key is not allowed to authorise this message")
+    );
+    Ok(())
+}
+
+
+
+/// Returns the owner if the public key belongs to a session key or `None` otherwise.
+/// Fails if the session key is expired, revoked or isn't allowed to authorise the given variant
+pub fn session_owner(
+    storage     :   &dyn Storage,
+    block       :   &BlockInfo,
+    pubkey      :   &[u8],
+    variant     :   Option<String>,
+) -> StdResult<Option<String>> {
+    match session_key(storage, block, pubkey)? {
+        Some(session) => {
+            ensure_session_allowed(&session, &[variant])?;
+            Ok(Some(session.owner))
+        },
+        None => Ok(None)
+    }
+}
+
+
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_create_session_key(
+    api         :   &dyn Api,
+    storage     :   &mut dyn Storage,
+    block       :   &BlockInfo,
+    owner       :   &str,
+    owner_id    :   Option<&[u8]>,
+    credential  :   CosmosCredential,
+    expires     :   Expiration,
+    allowed     :   Option<Vec<String>>,
+) -> StdResult<Response> {
+    let session = create_session_key(
+        api, storage, block, owner, owner_id, credential, expires, allowed
+    )?;
+
+    Ok(Response::new()
+      .add_attribute("action", "create_session_key")
+      .add_attribute("expires", session.expires.to_string())
+    )
+}
+
+
+
+pub fn handle_revoke_session_key(
+    storage     :   &mut dyn Storage,
+    owner       :   &str,
+) -> StdResult<Response> {
+    revoke_session_key(storage, owner)?;
+
+    Ok(Response::new()
+      .add_attribute("action", "revoke_session_key")
+    )
+}
+
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,217 @@
+use cosmwasm_std::{
+    coins, testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    to_binary, Binary, Env, OwnedDeps, MessageInfo, Uint128
+};
+use crate::{
+    errors::EncryptionError,
+    common::{handle_encrypted_wrapper, handle_encrypted_wrapper_with_context},
+    crypto::{client::{sign_arbitrary, EncryptedParamsBuilder}, pubkey_to_address, utils::secret_key_from_bytes},
+    fees::{fee_balance, handle_deposit_fees, RelayerFee},
+    gateway::GatewayExecuteMsg,
+    revocation::revoke_credentials,
+    test_utils::{encrypted_test_setup, CONTRACT_PUBLIC, SIGN_HRP},
+    traits::SCRT_DENOM,
+    types::{Duration, Expiration},
+    AddressScheme, CosmosCredential, EncryptedPayload
+};
+use super::*;
+
+
+const OWNER : &str = "secret1owner";
+const SESSION_PRIVATE : [u8; 32] = [3u8; 32];
+
+
+#[cosmwasm_schema::cw_serde]
+enum SessionMethods {
+    Allowed {},
+    Forbidden {},
+}
+
+
+type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+
+/// Builder of params signed by the session key
+fn session_builder() -> EncryptedParamsBuilder {
+    EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &SESSION_PRIVATE.to_vec()
+    ).unwrap()
+}
+
+
+/// Credential of the session key signing the given owner address
+fn session_credential(owner: &str) -> CosmosCredential {
+    let session_key = secret_key_from_bytes(&SESSION_PRIVATE.to_vec()).unwrap();
+    let pubkey = session_builder().signing_pubkey();
+    let address = pubkey_to_address(&pubkey, SIGN_HRP).unwrap();
+    CosmosCredential {
+        signature: sign_arbitrary(&session_key, &address, owner, &AddressScheme::Cosmos),
+        message: owner.to_string(),
+        pubkey,
+        hrp: SIGN_HRP.to_string(),
+        scheme: None
+    }
+}
+
+
+/// Payload of the session key carrying the given message
+fn session_payload(msg: &GatewayExecuteMsg<SessionMethods>, fee: Option<RelayerFee>) -> EncryptedPayload {
+    let user_pubkey = session_builder().signing_pubkey();
+    EncryptedPayload {
+        user_address: pubkey_to_address(&user_pubkey, SIGN_HRP).unwrap(),
+        user_pubkey,
+        hrp: SIGN_HRP.to_string(),
+        msg: to_binary(msg).unwrap(),
+        kind: None,
+        issued_at: None,
+        scheme: None,
+        fee,
+    }
+}
+
+
+fn send(
+    deps: &mut Deps,
+    env: &Env,
+    msg: GatewayExecuteMsg<SessionMethods>
+) -> Result<(GatewayExecuteMsg<SessionMethods>, MessageInfo), EncryptionError> {
+    let params = session_builder().build(&session_payload(&msg, None)).unwrap();
+    handle_encrypted_wrapper(
+        &deps.api,
+        &mut deps.storage,
+        env,
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<SessionMethods>::from(params)
+    )
+}
+
+
+/// Creates a session key of the owner valid for 10 blocks and allowed to call `allowed`
+fn setup(owner_id: Option<&Binary>) -> (Deps, Env) {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    encrypted_test_setup(&mut deps.storage);
+
+    set_max_session_duration(&mut deps.storage, Some(Duration::Height(10))).unwrap();
+    create_session_key(
+        &deps.api, &mut deps.storage, &env.block, OWNER, owner_id.map(|id| id.as_slice()),
+        session_credential(OWNER), Expiration::AtHeight(env.block.height + 10),
+        Some(vec!["allowed".to_string()])
+    ).unwrap();
+
+    (deps, env)
+}
+
+
+
+#[test]
+fn session_key_must_sign_owner() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    assert!(create_session_key(
+        &deps.api, &mut deps.storage, &env.block, "secret1other", None,
+        session_credential(OWNER), Expiration::AtTime(env.block.time.plus_seconds(60)), None
+    ).is_err());
+}
+
+
+#[test]
+fn session_key_must_expire() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    assert!(create_session_key(
+        &deps.api, &mut deps.storage, &env.block, OWNER, None,
+        session_credential(OWNER), Expiration::Never {}, None
+    ).is_err());
+}
+
+
+#[test]
+fn session_key_duration_limits() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let create = |deps: &mut Deps, expires: Expiration| create_session_key(
+        &deps.api, &mut deps.storage, &env.block, OWNER, None,
+        session_credential(OWNER), expires, None
+    );
+
+    // within a week by default
+    assert!(create(&mut deps, Expiration::AtTime(env.block.time.plus_seconds(8 * 24 * 60 * 60))).is_err());
+    // and in the same units as the maximal duration
+    assert!(create(&mut deps, Expiration::AtHeight(env.block.height + 10)).is_err());
+
+    set_max_session_duration(&mut deps.storage, Some(Duration::Height(5))).unwrap();
+    assert!(create(&mut deps, Expiration::AtHeight(env.block.height + 10)).is_err());
+    assert!(create(&mut deps, Expiration::AtHeight(env.block.height + 5)).is_ok());
+}
+
+
+#[test]
+fn session_key_acts_for_owner() {
+    let (mut deps, env) = setup(None);
+
+    let (_, info) = send(&mut deps, &env, GatewayExecuteMsg::Extension { msg: SessionMethods::Allowed {} }).unwrap();
+    assert_eq!(info.sender.as_str(), OWNER);
+}
+
+
+#[test]
+fn session_key_allowed_methods() {
+    let (mut deps, env) = setup(None);
+
+    // not in the allowed list
+    assert!(send(&mut deps, &env, GatewayExecuteMsg::Extension { msg: SessionMethods::Forbidden {} }).is_err());
+    // gateway methods can't be authorised by a session key
+    assert!(send(&mut deps, &env, GatewayExecuteMsg::RevokeSessionKey {}).is_err());
+}
+
+
+#[test]
+fn expired_session_key() {
+    let (mut deps, mut env) = setup(None);
+    env.block.height += 10;
+
+    assert!(send(&mut deps, &env, GatewayExecuteMsg::Extension { msg: SessionMethods::Allowed {} }).is_err());
+}
+
+
+#[test]
+fn session_key_cannot_spend_owner_fees() {
+    let (mut deps, env) = setup(None);
+    handle_deposit_fees(&mut deps.storage, OWNER, &coins(100, SCRT_DENOM)).unwrap();
+
+    let with_fee = session_builder().build(&session_payload(
+        &GatewayExecuteMsg::Extension { msg: SessionMethods::Allowed {} },
+        Some(RelayerFee { amount: Uint128::new(100), token: None, relayer: None }),
+    )).unwrap();
+
+    assert!(handle_encrypted_wrapper_with_context(
+        &deps.api,
+        &mut deps.storage,
+        &env,
+        mock_info("relayer", &[]),
+        GatewayExecuteMsg::<SessionMethods>::from(with_fee)
+    ).is_err());
+    assert_eq!(fee_balance(&deps.storage, OWNER, None), Uint128::new(100));
+}
+
+
+#[test]
+fn session_key_revoked_with_owner_credential() {
+    let owner_id = Binary(vec![2u8; 33]);
+    let (mut deps, env) = setup(Some(&owner_id));
+    let session_pubkey = session_builder().signing_pubkey();
+    let allowed = Some("allowed".to_string());
+
+    assert_eq!(
+        session_owner(&deps.storage, &env.block, &session_pubkey, allowed.clone()).unwrap(),
+        Some(OWNER.to_string())
+    );
+
+    // the credential that created the key is revoked
+    revoke_credentials(&mut deps.storage, &env.block, std::slice::from_ref(&owner_id)).unwrap();
+    assert!(session_owner(&deps.storage, &env.block, &session_pubkey, allowed).is_err());
+}
@@ -0,0 +1,54 @@
+use cosmwasm_std::{to_binary, Binary, Storage};
+use crate::{
+    common::ENCRYPTING_WALLET,
+    crypto::{client::EncryptedParamsBuilder, pubkey_to_address, wallets::SecretEncryptionWallet}, 
+    gateway::GatewayExecuteMsg, EncryptedPayload
+};
+
+pub const SIGN_HRP         : &str = "cosmos";
+pub const CONTRACT_PRIVATE : &str = "OIxm5RnQkzCDMMoXv9LIkmVhrr2+AfFfneOsmXfSwQ0=";
+pub const CONTRACT_PUBLIC  : &str = "A5OJoIXQRceFbnqUe2rJ3s2MKAFEiPrKZ86eHPyZvSl5";
+pub const USER_PRIVATE     : [u8; 32] = [7u8; 32];
+pub const ETH_PRIVATE      : &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+pub const ETH_ADDRESS      : &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+pub const ED25519_PRIVATE  : [u8; 32] = [11u8; 32];
+
+
+/// Saves the encrypting wallet of the gateway used by the encrypted test payloads
+pub fn encrypted_test_setup(storage: &mut dyn Storage) {
+    ENCRYPTING_WALLET.save(
+        storage,
+        &SecretEncryptionWallet {
+            private_key: Binary::from_base64(CONTRACT_PRIVATE).unwrap(),
+            public_key: Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        }
+    ).unwrap();
+}
+
+
+/// Builder of params encrypted by the test user to the gateway of [encrypted_test_setup]
+pub fn encrypted_test_builder() -> EncryptedParamsBuilder {
+    EncryptedParamsBuilder::new(
+        &Binary::from_base64(CONTRACT_PUBLIC).unwrap(),
+        &USER_PRIVATE.to_vec()
+    ).unwrap()
+}
+
+
+/// Payload of the test user carrying the given message
+pub fn encrypted_test_payload(
+    builder: &EncryptedParamsBuilder, 
+    msg: &GatewayExecuteMsg
+) -> EncryptedPayload {
+    let user_pubkey = builder.signing_pubkey();
+    EncryptedPayload {
+        user_address: pubkey_to_address(&user_pubkey, SIGN_HRP).unwrap(),
+        user_pubkey,
+        hrp: SIGN_HRP.to_string(),
+        msg: to_binary(msg).unwrap(),
+        kind: None,
+        issued_at: None,
+        scheme: None,
+        fee: None,
+    }
+}
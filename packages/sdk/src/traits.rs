@@ -8,6 +8,12 @@ pub const SCRT_DENOM: &str = "uscrt";
 pub trait WithEncryption : Serialize + Clone  {
     fn encrypted(&self)     -> EncryptedParams;
     fn is_encrypted(&self)  -> bool;
+
+    /// Name of the variant checked against the list allowed for a session key.
+    /// `None` if the message can't be authorised by a session key at all
+    fn session_variant(&self) -> Option<String> {
+        None
+    }
 }
 
 
@@ -26,12 +32,20 @@ impl<E> WithEncryption for crate::gateway::GatewayExecuteMsg<E>
                 payload_hash,
                 user_key,
                 nonce,
+                epoch,
+                version,
+                cipher,
+                associated_data,
             } => EncryptedParams {
                 payload,
                 payload_signature,
                 payload_hash,
                 user_key,
-                nonce
+                nonce,
+                epoch,
+                version,
+                cipher,
+                associated_data
             },
             _ => panic!("This message is not encrypted")
 
@@ -39,11 +53,53 @@ impl<E> WithEncryption for crate::gateway::GatewayExecuteMsg<E>
     }
 
     fn is_encrypted(&self)  -> bool {
-        if let crate::gateway::GatewayExecuteMsg::Encrypted{..} = self {
-            true
-        } else {
-            false
+        matches!(self, crate::gateway::GatewayExecuteMsg::Encrypted{..})
+    }
+
+    fn session_variant(&self) -> Option<String> {
+        match self {
+            crate::gateway::GatewayExecuteMsg::Extension { msg } => crate::session_key::variant_name(msg),
+            _ => None
         }
     }
 }
 
+
+
+
+#[cfg(feature = "gateway")]
+impl<I, A, E> WithEncryption for crate::gateway::GatewayQueryMsg<I, A, E> 
+    where I: Clone + JsonSchema + Serialize, A: Clone + JsonSchema + Serialize, E: Clone + JsonSchema + Serialize
+{
+    fn encrypted(&self)     -> EncryptedParams {
+        match self.clone() {
+            crate::gateway::GatewayQueryMsg::Encrypted {
+                payload,
+                payload_signature,
+                payload_hash,
+                user_key,
+                nonce,
+                epoch,
+                version,
+                cipher,
+                associated_data,
+            } => EncryptedParams {
+                payload,
+                payload_signature,
+                payload_hash,
+                user_key,
+                nonce,
+                epoch,
+                version,
+                cipher,
+                associated_data
+            },
+            _ => panic!("This message is not encrypted")
+
+        }
+    }
+
+    fn is_encrypted(&self)  -> bool {
+        matches!(self, crate::gateway::GatewayQueryMsg::Encrypted{..})
+    }
+}
@@ -1,5 +1,5 @@
 import { MsgExecuteContractParams, MsgInstantiateContractParams, MsgInstantiateContractResponse, TxResultCode } from "secretjs";
-import { Contract, CosmosCredential, InnerQueries, GatewayExecuteMsg as GatewayExecuteMsg, GatewaySimpleInitMsg, GatewayQueryMsg } from "./types";
+import { Contract, CosmosCredential, InnerQueries, GatewayExecuteMsg as GatewayExecuteMsg, GatewaySimpleInitMsg, GatewayQueryMsg, EncryptionKeyResponse } from "./types";
 import { loadCodeConfig, loadContractConfig } from "./config";
 import { getConsumerWallet, secretClient } from "./clients";
 import { getEncryptedSignedMsg } from "./crypto";
@@ -40,7 +40,7 @@ export const instantiateGatewaySimple = async () : Promise<Contract> => {
 
 export const getGatewayEncryptionKey = async () => {
     const res = await queryGateway({ encryption_key: {} });
-    return res as string;
+    return (res as EncryptionKeyResponse).public_key;
 }
 
 
@@ -62,7 +62,7 @@ export const queryGatewayAuth = (query: InnerQueries, credentials: CosmosCredent
         with_auth_data: {
             query,
             auth_data: {
-                credentials,
+                credentials: credentials.map(cosmos_arbitrary => ({ cosmos_arbitrary })),
             }
         }
     })
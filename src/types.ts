@@ -48,17 +48,87 @@ export type TokenData = {
 
 
 
+export type AddressScheme = "cosmos" | "eth_secp256k1";
+
+
 export type CosmosCredential = {    
     pubkey      :   string,
     signature   :   string,
     message     :   string,
-    hrp?        :   string
+    hrp?        :   string,
+    scheme?     :   AddressScheme
+}
+
+
+export type EthCredential = {    
+    address     :   string,
+    signature   :   string,
+    message     :   string,
+    pubkey?     :   string
+}
+
+
+export type Ed25519AddressFormat = "base58" | "hex";
+
+
+export type Ed25519Credential = {
+    pubkey      :   string,
+    signature   :   string,
+    message     :   string,
+    format?     :   Ed25519AddressFormat
+}
+
+
+export type MultisigCredential = {
+    threshold   :   number,
+    pubkeys     :   string[],
+    signatures  :   (string | null)[],
+    message     :   string,
+    hrp         :   string
+}
+
+
+export type PasskeyAssertion = {
+    credential_id       :   string,
+    authenticator_data  :   string,
+    client_data_json    :   string,
+    signature           :   string
 }
 
 
+export type PasskeyCredential = {
+    pubkey      :   string,
+    assertion   :   PasskeyAssertion,
+    message     :   string,
+    hrp         :   string
+}
+
+
+export type Credential = 
+    { cosmos_arbitrary: CosmosCredential }  |
+    { eth_personal_sign: EthCredential }   |
+    { eth_typed_data: EthCredential }   |
+    { passkey: PasskeyCredential }      |
+    { ed25519: Ed25519Credential }      |
+    { amino_multisig: MultisigCredential };
+
+
+export type CredentialKind = "cosmos_arbitrary" | "eth_personal_sign" | "eth_typed_data" | "passkey" | "ed25519";
+
+
 export type CosmosAuthData = {
-    credentials      :   CosmosCredential[],
-    primary_index?   :   number
+    credentials      :   Credential[],
+    primary_index?   :   number,
+    threshold?       :   number
+}
+
+
+// must be serialized with the keys in this order before signing
+export type LinkAccountMessage = {
+    link_account     :   string,
+    contract         :   string,
+    chain_id         :   string,
+    nonce            :   string
 }
 
 
@@ -68,6 +138,11 @@ export type Expiration =
     { never: {} };
 
 
+export type Duration = 
+    { height: number }  | 
+    { time: number };
+
+
 export type SessionConfig = {
     generate_on_auth?   :   boolean,
     can_view?           :   boolean,
@@ -78,8 +153,40 @@ export type SessionConfig = {
 
 
 
+export type FeegrantConfig = {
+    spend_limit?        :   string,
+    duration?           :   Duration,
+    shared              :   boolean,
+    max_grants?         :   number
+}
+
+
+
+export type FeegrantWalletResponse = {
+    address             :   string,
+    expires?            :   Expiration
+}
+
+
+
+export type SecretFeegrantWallet = {
+    address             :   string,
+    mnemonic            :   string
+}
+
+
+
+
+
 export type GatewaySimpleInitMsg = {
-    admin?                   :       string
+    admin?                   :       string,
+    nonce_validity?          :       Duration,
+    key_grace_period?        :       Duration,
+    key_rotation_period?     :       Duration,
+    max_session_duration?    :       Duration,
+    feegrant?                :       FeegrantConfig,
+    fee_tokens?              :       Snip20Token[],
+    passkey_rp_id?           :       string
 }
 
 
@@ -92,6 +199,9 @@ export type ExtendedMethods =
 
 export type InnerQueries = 
     { get_secret: {} }   |
+    { linked_credentials: {} }   |
+    { fee_balance: { token?: Snip20Token } }   |
+    { feegrant_wallet: {} }   |
     { test: {} }        
 
 
@@ -101,6 +211,36 @@ export type GatewayExecuteMsg =
 
     { reset_encryption_key: {} }         |
 
+    { prune_nonces: { limit?: number } } |
+
+    { create_session_key: { 
+        credential: CosmosCredential, 
+        expires: Expiration, 
+        allowed?: string[] 
+    }}                                   |
+
+    { revoke_session_key: {} }           |
+
+    { link_credentials: { credentials: Credential[], nonce: string } } |
+
+    { unlink_credentials: { ids: string[] } }           |
+
+    { revoke_credentials: { ids: string[] } }           |
+
+    { restore_credentials: { ids: string[] } }          |
+
+    { deposit_fees: { recipient?: string } }            |
+
+    { request_feegrant: {} }                            |
+
+    { revoke_feegrant: {} }                             |
+
+    { withdraw_fees: { 
+        token?: Snip20Token, 
+        amount?: string, 
+        recipient?: string 
+    }}                                   |
+
     { extension: { msg: ExtendedMethods } }           |
     
     { encrypted: { 
@@ -108,23 +248,86 @@ export type GatewayExecuteMsg =
         payload_signature: string, 
         payload_hash: string,
         user_key: string,
-        nonce: string 
+        nonce: string,
+        epoch?: number,
+        version?: number,
+        cipher?: Cipher,
+        associated_data?: AssociatedData
     }} 
     
 
+export type Cipher = "chacha20_poly1305" | "xchacha20_poly1305" | "aes256_gcm_siv"
+
+
+
+export type AssociatedData = "contract" | "contract_and_relayer"
+
+
+
+export type Snip20Token = {
+    address: string,
+    code_hash: string
+}
+
+
+
+export type RelayerFee = {
+    amount: string,
+    token?: Snip20Token,
+    relayer?: string
+}
+
+
+
 export type EncryptedPayload = {
     user_address: string,
     user_pubkey: string,
     hrp: string,
-    msg: string
+    msg: string,
+    kind?: CredentialKind,
+    issued_at?: Expiration,
+    scheme?: AddressScheme,
+    fee?: RelayerFee
 }
     
 
 
+export type EncryptionKeyResponse = {
+    public_key: string,
+    epoch: number,
+    expires?: Expiration
+}
+
+
+
+export type EncryptedResponse = {
+    data: string,
+    nonce: string
+}
+
+
+
 export type GatewayQueryMsg = 
 
     { encryption_key: {} }              |
 
+    { revoked_credentials: { 
+        page?: number, 
+        page_size?: number 
+    }}                                  |
+
+    { encrypted: { 
+        payload: string, 
+        payload_signature: string, 
+        payload_hash: string,
+        user_key: string,
+        nonce: string,
+        epoch?: number,
+        version?: number,
+        cipher?: Cipher,
+        associated_data?: AssociatedData
+    }}                                  |
+
     { with_permit: { 
         query: InnerQueries, 
         permit: Permit, 